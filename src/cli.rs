@@ -4,18 +4,121 @@ pub(crate) fn build_cli() -> Command {
     command!("bullboard")
         .propagate_version(true)
         .subcommand_required(true)
+        .arg(
+            arg!(--"base-currency" <CURRENCY> "the currency to report totals in. Defaults to BULLBOARD_BASE_CURRENCY")
+                .global(true),
+        )
+        .arg(
+            arg!(--account <ACCOUNT> "the account to use. Repeat it, or separate accounts by commas, to combine accounts in reports. Defaults to BULLBOARD_ACCOUNT")
+                .value_delimiter(',')
+                .action(clap::ArgAction::Append)
+                .global(true),
+        )
+        .arg(
+            arg!(--store <PATH> "where the events are stored: a SQLite database, or a JSON Lines file when the name ends in .jsonl. Defaults to BULLBOARD_DB_PATH")
+                .global(true),
+        )
+        .arg(
+            arg!(--format <FORMAT> "how to show the dashboard and journal: table, json or csv")
+                .value_parser(["table", "json", "csv"])
+                .default_value("table")
+                .global(true),
+        )
         .subcommand(Command::new("demo").about("Show a demo of the dashboard"))
         .subcommand(
             Command::new("add")
                 .about("Add a new event")
-                .arg(arg!(--type <TYPE> "the type of event to add"))
+                .arg(arg!(--type <TYPE> "the type of event to add: buy, sell, dividend, price, rate, split, deposit, withdraw or fee"))
                 .arg(arg!(--date <DATE> "the date of the event"))
-                .arg(arg!(--price <PRICE> "the price of the event"))
+                .arg(arg!(--price <PRICE> "the price of the event, or the sum deposited, withdrawn or charged"))
                 .arg(arg!(--currency <CURRENCY> "the currency of the event"))
-                .arg(arg!(--identifier <IDENTIFIER> "the ID (symbol) of the asset"))
-                .arg(arg!(--amount <AMOUNT> "the amount of the event").default_value("1")),
+                .arg(arg!(--identifier <IDENTIFIER> "the ID (symbol) of the asset, or the currency for a rate"))
+                .arg(arg!(--amount <AMOUNT> "the amount of the event").default_value("1"))
+                .arg(arg!(--ratio <RATIO> "the ratio of a split, new stocks:old stocks, e.g. 4:1"))
+                .arg(arg!(--fee <FEE> "the fee or commission paid for a buy or sell, in the currency of the event"))
+                .arg(arg!(--tax <TAX> "the tax withheld per share on a dividend, in the currency of the event"))
+                .arg(arg!(--country <COUNTRY> "the country that withheld the tax on a dividend, e.g. US"))
+                .arg(arg!(--comment <COMMENT> "a note to keep with the event")),
+        )
+        .subcommand(
+            Command::new("void")
+                .about("Void an event that was added by mistake")
+                .arg(arg!(<ID> "the id of the event to void, as listed by the events command"))
+                .arg(arg!(--date <DATE> "the date of the correction"))
+                .arg(arg!(--comment <COMMENT> "why the event is voided")),
+        )
+        .subcommand(
+            Command::new("amend")
+                .about("Amend the price or amount of an event")
+                .arg(arg!(<ID> "the id of the event to amend, as listed by the events command"))
+                .arg(arg!(--price <PRICE> "the correct price, or sum deposited, withdrawn or charged"))
+                .arg(arg!(--currency <CURRENCY> "the currency of the correct price"))
+                .arg(arg!(--amount <AMOUNT> "the correct amount of a buy or sell"))
+                .arg(arg!(--date <DATE> "the date of the correction"))
+                .arg(arg!(--comment <COMMENT> "why the event is amended")),
+        )
+        .subcommand(
+            Command::new("accounts")
+                .about("Manage the accounts, such as a brokerage and a pension account")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("List the accounts"))
+                .subcommand(
+                    Command::new("create")
+                        .about("Create an account")
+                        .arg(arg!(<NAME> "the name of the account")),
+                ),
+        )
+        .subcommand(Command::new("events").about("List the stored events with their ids"))
+        .subcommand(
+            Command::new("import")
+                .about("Import buys, dividends and prices from a broker CSV export")
+                .arg(arg!(<FILE> "the CSV file to import"))
+                .arg(arg!(--profile <PROFILE> "a JSON file that maps the columns of the CSV onto events"))
+                .arg(arg!(--"dry-run" "show the events that would be imported, without storing them")),
+        )
+        .subcommand(
+            Command::new("prices")
+                .about("Manage the prices of assets")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("import")
+                        .about("Import a price history from a CSV file with the columns date, ticker, close and currency")
+                        .arg(arg!(<FILE> "the CSV file to import")),
+                )
+                .subcommand(
+                    Command::new("fetch")
+                        .about("Fetch the price of every asset held")
+                        .arg(arg!(--quotes <FILE> "a JSON or CSV file with quotes to read the prices from. Defaults to BULLBOARD_QUOTES_FILE"))
+                        .arg(arg!(--date <DATE> "fetch the prices on this date instead of the latest prices")),
+                ),
+        )
+        .subcommand(
+            Command::new("journal")
+                .about("Show the journal")
+                .arg(arg!(--ticker <TICKER> "only show the history of this asset"))
+                .arg(arg!(--from <DATE> "only show the events on or after this date"))
+                .arg(arg!(--until <DATE> "only show the events on or before this date")),
+        )
+        .subcommand(
+            Command::new("withholding")
+                .about("Show the tax withheld on dividends per country and year"),
+        )
+        .subcommand(
+            Command::new("dashboard")
+                .about("Show the dashboard")
+                .arg(
+                    arg!(--"cost-basis" <METHOD> "how to pick the stocks sold: fifo, lifo or average. Defaults to BULLBOARD_COST_BASIS or fifo")
+                        .value_parser(["fifo", "lifo", "average"]),
+                )
+                .arg(arg!(--"as-of" <DATE> "show the dashboard as it was at the end of this date. Defaults to today")),
         )
-        .subcommand(Command::new("journal").about("Show the journal"))
-        .subcommand(Command::new("dashboard").about("Show the dashboard"))
         .subcommand(Command::new("init").about("Initialize the event store"))
+        .subcommand(
+            Command::new("verify")
+                .about("Check that no stored event was changed or removed, using the hash chain of every account"),
+        )
+        .subcommand(
+            Command::new("migrate")
+                .about("Rewrite the events stored by an older version into the current format"),
+        )
 }
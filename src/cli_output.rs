@@ -1,11 +1,13 @@
 use std::fmt::Display;
 
-use prettytable::{format::FormatBuilder, row, Table};
+use prettytable::{cell, format::FormatBuilder, row, Table};
 
 use crate::{
     dashboard::Dashboard,
-    journal::{Journal, JournalEntry, JournalRow, JournalRowType},
-    value_objects::{Amounts, Asset},
+    envelope::EventLog,
+    journal::{Journal, JournalRow, JournalRowType},
+    value_objects::{Amount, Amounts, Asset},
+    withholding::WithholdingReport,
 };
 
 impl Display for Dashboard {
@@ -18,7 +20,7 @@ impl Display for Dashboard {
             f,
             "\nDashboard\n\n{}\n{}",
             format_meta_table(self),
-            format_portfolio_table(assets)
+            format_portfolio_table(self, assets)
         )
     }
 }
@@ -32,22 +34,66 @@ impl Display for Journal {
             .build();
 
         table.set_format(clean_more_padding);
-        table.set_titles(
-            row![c->"Date", c->"Type", c->"Ticker", c->"Amount", c->"Price", c->"Total"],
-        );
-        self.entries.iter().for_each(|entry| match entry {
-            JournalEntry::Buy(journal_row) => {
-                table.add_row(journal_row_to_row(journal_row));
-            }
-            JournalEntry::Dividend(journal_row) => {
-                table.add_row(journal_row_to_row(journal_row));
-            }
-        });
+        let mut titles =
+            row![c->"Date", c->"Type", c->"Ticker", c->"Amount", c->"Price", c->"Total"];
+        if let Some(base_currency) = self.base_currency() {
+            titles.add_cell(cell!(c->format!("Total in {}", base_currency)));
+        }
+        table.set_titles(titles);
+        for entry in &self.entries {
+            table.add_row(journal_row_to_row(self, entry.row()));
+        }
         write!(f, "\nMy Journal\n{}", table)
     }
 }
 
-fn journal_row_to_row(journal_row: &JournalRow) -> prettytable::Row {
+impl Display for WithholdingReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut table = prettytable::Table::new();
+        let clean_more_padding = FormatBuilder::new()
+            .column_separator(' ')
+            .padding(2, 1)
+            .build();
+
+        table.set_format(clean_more_padding);
+        table.set_titles(row![c->"Country", c->"Year", c->"Gross", c->"Withheld tax", c->"Net"]);
+        for withholding in &self.rows {
+            table.add_row(row![
+                l->withholding.country.as_deref().unwrap_or("Unknown"),
+                l->withholding.year,
+                r->withholding.gross,
+                r->withholding.withheld_tax,
+                r->withholding.net()
+            ]);
+        }
+        write!(f, "\nDividend withholding\n{}", table)
+    }
+}
+
+impl Display for EventLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut table = prettytable::Table::new();
+        let clean_more_padding = FormatBuilder::new()
+            .column_separator(' ')
+            .padding(2, 1)
+            .build();
+
+        table.set_format(clean_more_padding);
+        table.set_titles(row![c->"Id", c->"Date", c->"Type", c->"Source", c->"Comment"]);
+        for envelope in &self.envelopes {
+            table.add_row(row![
+                l->envelope.id,
+                l->envelope.event.created_at().format("%Y-%m-%d"),
+                l->envelope.event_type,
+                l->envelope.source.as_deref().unwrap_or(""),
+                l->envelope.comment.as_deref().unwrap_or("")
+            ]);
+        }
+        write!(f, "\nEvents\n{}", table)
+    }
+}
+
+fn journal_row_to_row(journal: &Journal, journal_row: &JournalRow) -> prettytable::Row {
     dbg!(&journal_row);
     let date_s = if let Some(date) = journal_row.date {
         date.format("%Y-%m-%d").to_string()
@@ -55,21 +101,42 @@ fn journal_row_to_row(journal_row: &JournalRow) -> prettytable::Row {
         "".to_string()
     };
 
-    row![
+    // The amount of a split is the number of new stocks per old stock
+    let amount_s = match journal_row.rtype {
+        JournalRowType::Split => format!("x{}", journal_row.amount),
+        _ => journal_row.amount.to_string(),
+    };
+
+    let mut row = row![
         l->date_s,
         l->journal_row.rtype,
         l->journal_row.identifier,
-        r->journal_row.amount,
-        r->journal_row.price,
-        r->journal_row.total
-    ]
+        r->amount_s,
+        r->fmt_optional_amount(&journal_row.price),
+        r->fmt_optional_amount(&journal_row.total)
+    ];
+    if journal.base_currency().is_some() {
+        let converted = match journal_row.total {
+            Some(_) => fmt_converted_amount(&journal.converted_total(journal_row)),
+            None => "".to_string(),
+        };
+        row.add_cell(cell!(r->converted));
+    }
+    row
 }
 
 impl Display for JournalRowType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             JournalRowType::Buy => write!(f, "Buy"),
+            JournalRowType::Sell => write!(f, "Sell"),
             JournalRowType::Dividend => write!(f, "Dividend"),
+            JournalRowType::Split => write!(f, "Split"),
+            JournalRowType::Deposit => write!(f, "Deposit"),
+            JournalRowType::Withdrawal => write!(f, "Withdrawal"),
+            JournalRowType::Fee => write!(f, "Fee"),
+            JournalRowType::Void => write!(f, "Void"),
+            JournalRowType::Amend => write!(f, "Amend"),
         }
     }
 }
@@ -91,18 +158,59 @@ fn format_meta_table(dashboard: &Dashboard) -> String {
             "Total buying price",
             fmt_amounts(&dashboard.total_buying_price),
         ),
+        ("Cash", fmt_amounts(&dashboard.cash)),
         ("Total value", fmt_amounts(&dashboard.total_value)),
         ("Total dividend", fmt_amounts(&dashboard.total_dividend)),
+        (
+            "Total withheld tax",
+            fmt_amounts(&dashboard.total_withheld_tax),
+        ),
+        ("Total fees", fmt_amounts(&dashboard.total_fees)),
+        (
+            "Total unrealized P/L",
+            fmt_amounts(&dashboard.total_unrealized_profit_loss),
+        ),
+        (
+            "Total realized P/L",
+            fmt_amounts(&dashboard.total_realized_profit_loss),
+        ),
+        ("Total P/L", fmt_amounts(&dashboard.total_profit_loss)),
     ];
 
     for (key, value) in meta {
         table.add_row(row![key, r->value]);
     }
 
+    if let Some(base_currency) = dashboard.base_currency() {
+        let total_value = dashboard.consolidate(&dashboard.total_value);
+        let total_profit_loss = dashboard.consolidate(&dashboard.total_profit_loss);
+        let mut missing_rates = vec![];
+
+        for (key, consolidated) in [
+            ("Total value", total_value),
+            ("Total P/L", total_profit_loss),
+        ] {
+            let consolidated = consolidated.expect("base currency is set");
+            table.add_row(row![format!("{} in {}", key, base_currency), r->consolidated.total]);
+            missing_rates.extend(consolidated.missing_rates);
+        }
+
+        missing_rates.sort();
+        missing_rates.dedup();
+        if !missing_rates.is_empty() {
+            let currencies = missing_rates
+                .iter()
+                .map(|currency| currency.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            table.add_row(row!["Missing exchange rates", r->currencies]);
+        }
+    }
+
     table.to_string()
 }
 
-fn format_portfolio_table(assets: Vec<Asset>) -> String {
+fn format_portfolio_table(dashboard: &Dashboard, assets: Vec<Asset>) -> String {
     let mut table = Table::new();
     let clean_more_padding = FormatBuilder::new()
         .column_separator(' ')
@@ -110,23 +218,91 @@ fn format_portfolio_table(assets: Vec<Asset>) -> String {
         .build();
 
     table.set_format(clean_more_padding);
-    table.set_titles(row![c->"Ticker", c->"Amount", c->"Dividend", c->"Value"]);
+    let mut titles = row![
+        c->"Ticker",
+        c->"Amount",
+        c->"Dividend",
+        c->"Tax",
+        c->"Net dividend",
+        c->"Value",
+        c->"Unrealized P/L",
+        c->"Realized P/L",
+        c->"Total P/L",
+        c->"Change"
+    ];
+    if let Some(base_currency) = dashboard.base_currency() {
+        titles.add_cell(cell!(c->format!("Value in {}", base_currency)));
+    }
+    table.set_titles(titles);
 
     for asset in assets {
-        table.add_row(row![
+        let mut row = row![
             d->asset.identifier,
             r->asset.amount,
             r->asset.dividends,
+            r->asset.withheld_tax,
+            r->asset.net_dividends(),
+            r->fmt_unknown_amount(&asset.value),
+            r->fmt_unknown_amount(&asset.unrealized_profit_loss()),
+            r->asset.realized_profit_loss,
+            r->fmt_unknown_amount(&asset.total_profit_loss()),
             r->asset
-                .value
-                .map(|v| v.to_string())
-                .unwrap_or("??.?? ???".to_string())
-        ]);
+                .price_change_percentage()
+                .map(|change| format!("{:.2}%", change))
+                .unwrap_or_default()
+        ];
+        if dashboard.base_currency().is_some() {
+            let converted_value = asset.value.as_ref().map(|value| dashboard.convert(value));
+            let converted_value = match converted_value {
+                Some(converted) => fmt_converted_amount(&converted),
+                None => fmt_unknown_amount(&None),
+            };
+            row.add_cell(cell!(r->converted_value));
+        }
+        table.add_row(row);
+    }
+
+    for cash in dashboard
+        .cash
+        .sorted()
+        .iter()
+        .filter(|cash| !cash.num.is_zero())
+    {
+        let mut row =
+            row![d->"Cash", r->"", r->"", r->"", r->"", r->cash, r->"", r->"", r->"", r->""];
+        if dashboard.base_currency().is_some() {
+            row.add_cell(cell!(r->fmt_converted_amount(&dashboard.convert(cash))));
+        }
+        table.add_row(row);
     }
 
     table.to_string()
 }
 
+/// Format an amount that is None when the price has not been obtained yet
+fn fmt_unknown_amount(amount: &Option<Amount>) -> String {
+    amount
+        .as_ref()
+        .map(|amt| amt.to_string())
+        .unwrap_or("??.?? ???".to_string())
+}
+
+/// Format an amount that is None when there is none, like the price of a split
+fn fmt_optional_amount(amount: &Option<Amount>) -> String {
+    amount
+        .as_ref()
+        .map(|amt| amt.to_string())
+        .unwrap_or_default()
+}
+
+/// Format an amount that is None when no exchange rate is known
+fn fmt_converted_amount(amount: &Option<Amount>) -> String {
+    amount
+        .as_ref()
+        .map(|amt| amt.to_string())
+        .unwrap_or("no rate".to_string())
+}
+
 fn fmt_amounts(amounts: &Amounts) -> String {
     amounts
         .sorted()
@@ -0,0 +1,251 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::value_objects::{Amount, Currency, SplitRatio};
+
+/// The method used to determine which stocks are sold, and so what they cost
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CostBasisMethod {
+    /// First in, first out: the oldest stocks are sold first
+    #[default]
+    Fifo,
+    /// Last in, first out: the newest stocks are sold first
+    Lifo,
+    /// Every stock costs the weighted average of all buying prices
+    Average,
+}
+
+impl FromStr for CostBasisMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fifo" => Ok(Self::Fifo),
+            "lifo" => Ok(Self::Lifo),
+            "average" => Ok(Self::Average),
+            _ => Err(format!(
+                "Unknown cost basis method {}. Use fifo, lifo or average",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for CostBasisMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CostBasisMethod::Fifo => write!(f, "fifo"),
+            CostBasisMethod::Lifo => write!(f, "lifo"),
+            CostBasisMethod::Average => write!(f, "average"),
+        }
+    }
+}
+
+/// A number of stocks bought at once, for the same price
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lot {
+    /// The time the stocks were bought
+    pub bought_at: NaiveDateTime,
+    /// The amount of stocks left in this lot
+    pub amount: f64,
+    /// The price paid for each stock
+    pub price: Amount,
+}
+
+impl Lot {
+    pub fn cost(&self) -> Amount {
+        self.price.clone() * self.amount
+    }
+}
+
+/// All lots held of one asset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    currency: Currency,
+    lots: Vec<Lot>,
+}
+
+impl Position {
+    pub fn new(currency: Currency) -> Self {
+        Self {
+            currency,
+            lots: vec![],
+        }
+    }
+
+    /// The currency all lots were bought in
+    pub fn currency(&self) -> &Currency {
+        &self.currency
+    }
+
+    pub fn lots(&self) -> &[Lot] {
+        &self.lots
+    }
+
+    /// The amount of stocks held over all lots
+    pub fn amount(&self) -> f64 {
+        self.lots.iter().map(|lot| lot.amount).sum()
+    }
+
+    /// The total price paid for the stocks still held
+    pub fn cost_basis(&self) -> Amount {
+        self.lots
+            .iter()
+            .fold(Amount::zero(self.currency.clone()), |total, lot| {
+                total + lot.cost()
+            })
+    }
+
+    pub fn buy(&mut self, lot: Lot) {
+        self.lots.push(lot);
+    }
+
+    /// Multiply the amount in every lot by the ratio and divide the price by it,
+    /// so the cost basis stays the same.
+    pub fn split(&mut self, ratio: SplitRatio) {
+        for lot in self.lots.iter_mut() {
+            lot.amount *= ratio.factor();
+            lot.price = ratio.adjust_price(&lot.price);
+        }
+    }
+
+    /// Take the amount of stocks out of the lots, picked by the method.
+    /// Returns the cost basis of the stocks sold.
+    pub fn sell(&mut self, amount: f64, method: CostBasisMethod) -> Amount {
+        match method {
+            CostBasisMethod::Fifo => self.sell_in_order(amount, false),
+            CostBasisMethod::Lifo => self.sell_in_order(amount, true),
+            CostBasisMethod::Average => self.sell_average(amount),
+        }
+    }
+
+    fn sell_in_order(&mut self, amount: f64, newest_first: bool) -> Amount {
+        let mut sold_cost = Amount::zero(self.currency.clone());
+        let mut remaining = amount;
+
+        if newest_first {
+            self.lots.reverse();
+        }
+        for lot in self.lots.iter_mut() {
+            if remaining <= 0.0 {
+                break;
+            }
+            let taken = lot.amount.min(remaining);
+            sold_cost += lot.price.clone() * taken;
+            lot.amount -= taken;
+            remaining -= taken;
+        }
+        if newest_first {
+            self.lots.reverse();
+        }
+
+        self.lots.retain(|lot| lot.amount > 0.0);
+        sold_cost
+    }
+
+    /// Every lot is reduced by the same fraction, so the average price of the
+    /// remaining stocks stays the same.
+    fn sell_average(&mut self, amount: f64) -> Amount {
+        let held = self.amount();
+        if held <= 0.0 {
+            return Amount::zero(self.currency.clone());
+        }
+
+        let sold_fraction = (amount / held).min(1.0);
+        let sold_cost = self.cost_basis() * sold_fraction;
+        for lot in self.lots.iter_mut() {
+            lot.amount -= lot.amount * sold_fraction;
+        }
+
+        self.lots.retain(|lot| lot.amount > 0.0);
+        sold_cost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::date_utils::fixtures::iphone_launched_at;
+
+    use super::*;
+
+    #[test]
+    fn test_cost_basis_method_from_str() {
+        assert_eq!("fifo".parse(), Ok(CostBasisMethod::Fifo));
+        assert_eq!("LIFO".parse(), Ok(CostBasisMethod::Lifo));
+        assert_eq!("average".parse(), Ok(CostBasisMethod::Average));
+        assert!("hifo".parse::<CostBasisMethod>().is_err());
+    }
+
+    #[test]
+    fn test_position_cost_basis() {
+        let position = position();
+        assert_eq!(position.amount(), 20.0);
+        assert_eq!(position.cost_basis(), Amount::from("300.00 USD"));
+    }
+
+    #[test]
+    fn test_position_sell_fifo() {
+        let mut position = position();
+        let sold_cost = position.sell(15.0, CostBasisMethod::Fifo);
+
+        assert_eq!(sold_cost, Amount::from("200.00 USD"));
+        assert_eq!(position.amount(), 5.0);
+        assert_eq!(position.cost_basis(), Amount::from("100.00 USD"));
+    }
+
+    #[test]
+    fn test_position_sell_lifo() {
+        let mut position = position();
+        let sold_cost = position.sell(15.0, CostBasisMethod::Lifo);
+
+        assert_eq!(sold_cost, Amount::from("250.00 USD"));
+        assert_eq!(position.amount(), 5.0);
+        assert_eq!(position.cost_basis(), Amount::from("50.00 USD"));
+    }
+
+    #[test]
+    fn test_position_sell_average() {
+        let mut position = position();
+        let sold_cost = position.sell(15.0, CostBasisMethod::Average);
+
+        assert_eq!(sold_cost, Amount::from("225.00 USD"));
+        assert_eq!(position.amount(), 5.0);
+        assert_eq!(position.cost_basis(), Amount::from("75.00 USD"));
+    }
+
+    #[test]
+    fn test_position_sell_all_removes_lots() {
+        let mut position = position();
+        position.sell(20.0, CostBasisMethod::Fifo);
+
+        assert!(position.lots().is_empty());
+        assert_eq!(position.cost_basis(), Amount::zero(Currency::from("USD")));
+    }
+
+    #[test]
+    fn test_position_split_keeps_cost_basis() {
+        let mut position = position();
+        position.split(SplitRatio::from("4:1"));
+
+        assert_eq!(position.amount(), 80.0);
+        assert_eq!(position.cost_basis(), Amount::from("300.00 USD"));
+        assert_eq!(position.lots()[0].price, Amount::from("2.50 USD"));
+    }
+
+    fn position() -> Position {
+        let mut position = Position::new(Currency::from("USD"));
+        position.buy(Lot {
+            bought_at: iphone_launched_at(),
+            amount: 10.0,
+            price: Amount::from("10.00 USD"),
+        });
+        position.buy(Lot {
+            bought_at: iphone_launched_at() + chrono::Duration::days(1),
+            amount: 10.0,
+            price: Amount::from("20.00 USD"),
+        });
+        position
+    }
+}
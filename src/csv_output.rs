@@ -0,0 +1,180 @@
+use crate::{
+    dashboard::Dashboard,
+    journal::Journal,
+    value_objects::{Amount, Asset},
+};
+
+/// One row per asset, sorted by ticker. Every amount is split into a number and a currency column.
+pub fn dashboard_to_csv(dashboard: &Dashboard) -> Result<String, csv::Error> {
+    let mut assets: Vec<Asset> = dashboard.assets();
+    assets.sort_by(|a, b| a.identifier.ticker.cmp(&b.identifier.ticker));
+
+    let mut headers = vec!["ticker".to_string(), "amount".to_string()];
+    for name in [
+        "dividend",
+        "withheld_tax",
+        "net_dividend",
+        "cost_basis",
+        "value",
+        "unrealized_profit_loss",
+        "realized_profit_loss",
+        "total_profit_loss",
+    ] {
+        headers.extend(amount_headers(name));
+    }
+    if dashboard.base_currency().is_some() {
+        headers.extend(amount_headers("value_in_base_currency"));
+    }
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(&headers)?;
+    for asset in assets {
+        let mut record = vec![asset.identifier.to_string(), asset.amount.to_string()];
+        for amount in [
+            Some(asset.dividends.clone()),
+            Some(asset.withheld_tax.clone()),
+            Some(asset.net_dividends()),
+            Some(asset.cost_basis.clone()),
+            asset.value.clone(),
+            asset.unrealized_profit_loss(),
+            Some(asset.realized_profit_loss.clone()),
+            asset.total_profit_loss(),
+        ] {
+            record.extend(amount_fields(&amount));
+        }
+        if dashboard.base_currency().is_some() {
+            let converted = asset
+                .value
+                .as_ref()
+                .and_then(|value| dashboard.convert(value));
+            record.extend(amount_fields(&converted));
+        }
+        writer.write_record(&record)?;
+    }
+
+    into_string(writer)
+}
+
+/// One row per journal row, in the order of the journal, with ISO 8601 dates
+pub fn journal_to_csv(journal: &Journal) -> Result<String, csv::Error> {
+    let mut headers = vec![
+        "date".to_string(),
+        "type".to_string(),
+        "ticker".to_string(),
+        "amount".to_string(),
+    ];
+    headers.extend(amount_headers("price"));
+    headers.extend(amount_headers("total"));
+    if journal.base_currency().is_some() {
+        headers.extend(amount_headers("total_in_base_currency"));
+    }
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(&headers)?;
+    for entry in &journal.entries {
+        let row = entry.row();
+        let mut record = vec![
+            row.date
+                .map(|date| date.format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+            row.rtype.to_string(),
+            row.identifier.to_string(),
+            row.amount.to_string(),
+        ];
+        record.extend(amount_fields(&row.price));
+        record.extend(amount_fields(&row.total));
+        if journal.base_currency().is_some() {
+            record.extend(amount_fields(&journal.converted_total(row)));
+        }
+        writer.write_record(&record)?;
+    }
+
+    into_string(writer)
+}
+
+fn amount_headers(name: &str) -> [String; 2] {
+    [name.to_string(), format!("{}_currency", name)]
+}
+
+/// An amount as a number and a currency field. Empty when the amount is unknown
+fn amount_fields(amount: &Option<Amount>) -> [String; 2] {
+    match amount {
+        Some(amount) => [amount.num.to_string(), amount.currency.to_string()],
+        None => [String::new(), String::new()],
+    }
+}
+
+fn into_string(writer: csv::Writer<Vec<u8>>) -> Result<String, csv::Error> {
+    let bytes = writer
+        .into_inner()
+        .map_err(|err| csv::Error::from(err.into_error()))?;
+    Ok(String::from_utf8_lossy(&bytes).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{date_utils::fixtures::iphone_launched_at, events::AccountEvent};
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_journal_to_csv() {
+        let events = vec![
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "50.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_dividend_paid(
+                iphone_launched_at(),
+                "1.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+        ];
+
+        assert_eq!(
+            journal_to_csv(&Journal::new(events)).unwrap(),
+            "date,type,ticker,amount,price,price_currency,total,total_currency\n\
+             2007-01-09,Buy,AAPL,10,50.00,USD,500.00,USD\n\
+             2007-01-09,Dividend,AAPL,1,1.00,USD,1.00,USD\n"
+        );
+    }
+
+    #[test]
+    fn test_journal_to_csv_leaves_the_price_of_a_split_empty() {
+        let events = vec![AccountEvent::new_stock_split(
+            iphone_launched_at(),
+            "4:1".to_string(),
+            "AAPL".to_string(),
+        )];
+
+        assert_eq!(
+            journal_to_csv(&Journal::new(events)).unwrap(),
+            "date,type,ticker,amount,price,price_currency,total,total_currency\n\
+             2007-01-09,Split,AAPL,4,,,,\n"
+        );
+    }
+
+    #[test]
+    fn test_dashboard_to_csv_leaves_unknown_amounts_empty() {
+        let events = vec![AccountEvent::new_stocks_bought(
+            iphone_launched_at(),
+            10.0,
+            "50.00 USD".to_string(),
+            "AAPL".to_string(),
+        )];
+        let csv = dashboard_to_csv(&Dashboard::new(events)).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "ticker,amount,dividend,dividend_currency,withheld_tax,withheld_tax_currency,\
+             net_dividend,net_dividend_currency,cost_basis,cost_basis_currency,value,value_currency,\
+             unrealized_profit_loss,unrealized_profit_loss_currency,realized_profit_loss,\
+             realized_profit_loss_currency,total_profit_loss,total_profit_loss_currency"
+        );
+        assert_eq!(lines[1], "AAPL,10,0,USD,0,USD,0,USD,500.00,USD,,,,,0,USD,,");
+    }
+}
@@ -1,40 +1,140 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, error::Error, fmt::Display};
 
-use crate::events::{AccountEvent, DividendPaid, PriceObtained, StocksBought};
-use crate::value_objects::{Amount, Amounts, Asset, StockIdentifier};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+use crate::cost_basis::{CostBasisMethod, Lot, Position};
+use crate::events::{
+    AccountEvent, CashDeposited, CashWithdrawn, DividendPaid, ExchangeRateObtained, FeeCharged,
+    PriceObtained, StockSplit, StocksBought, StocksSold,
+};
+use crate::exchange_rates::{Consolidated, ExchangeRates};
+use crate::value_objects::{Amount, Amounts, Asset, Currency, StockIdentifier};
+
+/// Serialized into snapshots, see the snapshot module
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Dashboard {
-    events: Vec<AccountEvent>,
     pub number_of_positions: f64,
     pub total_dividend: Amounts,
     pub total_buying_price: Amounts,
     pub total_value: Amounts,
+    pub total_unrealized_profit_loss: Amounts,
+    pub total_realized_profit_loss: Amounts,
+    pub total_profit_loss: Amounts,
+    /// The cash balance, per currency
+    pub cash: Amounts,
+    /// The fees paid for trades and charged on the account
+    pub total_fees: Amounts,
+    /// The tax withheld on dividends
+    pub total_withheld_tax: Amounts,
+    #[serde(with = "crate::snapshot::map_as_pairs")]
     assets: HashMap<StockIdentifier, Asset>,
+    /// The lots of stocks currently at hand, per asset
+    #[serde(with = "crate::snapshot::map_as_pairs")]
+    positions: HashMap<StockIdentifier, Position>,
+    cost_basis_method: CostBasisMethod,
+    exchange_rates: ExchangeRates,
+    /// The currency to report consolidated totals in
+    #[serde(skip)]
+    base_currency: Option<Currency>,
 }
 
 impl Dashboard {
     pub fn new(events: Vec<AccountEvent>) -> Self {
-        let mut dashboard = Dashboard {
-            events,
+        Self::with_cost_basis_method(events, CostBasisMethod::default())
+    }
+
+    /// Build the dashboard, realizing profit and loss on sales with the given method
+    pub fn with_cost_basis_method(
+        events: Vec<AccountEvent>,
+        cost_basis_method: CostBasisMethod,
+    ) -> Self {
+        let dashboard = Dashboard {
             number_of_positions: 0.0,
             total_dividend: Amounts::zero(),
             total_buying_price: Amounts::zero(),
             total_value: Amounts::zero(),
+            total_unrealized_profit_loss: Amounts::zero(),
+            total_realized_profit_loss: Amounts::zero(),
+            total_profit_loss: Amounts::zero(),
+            cash: Amounts::zero(),
+            total_fees: Amounts::zero(),
+            total_withheld_tax: Amounts::zero(),
             assets: HashMap::new(),
+            positions: HashMap::new(),
+            cost_basis_method,
+            exchange_rates: ExchangeRates::default(),
+            base_currency: None,
         };
+        dashboard.replay(events)
+    }
 
-        for event in &dashboard.events.clone() {
-            dashboard.handle_event(event);
+    /// Fold more events into the dashboard, e.g. the ones stored after a snapshot of it
+    pub fn replay(mut self, events: impl IntoIterator<Item = AccountEvent>) -> Self {
+        for event in events {
+            self.handle_event(&event);
         }
+        self
+    }
 
-        dashboard
+    /// Report consolidated totals in the given currency, converted at the last rates obtained
+    pub fn in_base_currency(mut self, base_currency: Currency) -> Self {
+        self.base_currency = Some(base_currency);
+        self
     }
 
     pub fn assets(&self) -> Vec<Asset> {
         self.assets.values().cloned().collect()
     }
 
+    pub fn base_currency(&self) -> Option<&Currency> {
+        self.base_currency.as_ref()
+    }
+
+    /// Convert the amount into the base currency.
+    /// None means that there is no base currency, or no rate is known.
+    pub fn convert(&self, amount: &Amount) -> Option<Amount> {
+        self.base_currency
+            .as_ref()
+            .and_then(|base_currency| self.exchange_rates.convert(amount, base_currency))
+    }
+
+    /// Add up the amounts into the base currency.
+    /// None means that there is no base currency.
+    pub fn consolidate(&self, amounts: &Amounts) -> Option<Consolidated> {
+        self.base_currency
+            .as_ref()
+            .map(|base_currency| self.exchange_rates.consolidate(amounts, base_currency))
+    }
+
+    /// Whether the event can be folded into the dashboard as it is now
+    pub fn check(&self, event: &AccountEvent) -> Result<(), InvalidEvent> {
+        match event {
+            AccountEvent::StocksBought(event) => {
+                if let Some(position) = self.positions.get(&event.identifier) {
+                    if *position.currency() != event.currency() {
+                        return Err(InvalidEvent::CurrencyMismatch {
+                            identifier: event.identifier.clone(),
+                            held_in: position.currency().clone(),
+                            bought_in: event.currency(),
+                        });
+                    }
+                }
+            }
+            AccountEvent::StocksSold(event) => {
+                let held = self.amount_of(&event.identifier);
+                if event.amount > held {
+                    return Err(InvalidEvent::Oversold {
+                        identifier: event.identifier.clone(),
+                        held,
+                        sold: event.amount,
+                    });
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
     fn amount_of(&self, identifier: &StockIdentifier) -> f64 {
         self.assets
             .get(identifier)
@@ -42,74 +142,250 @@ impl Dashboard {
             .amount
     }
 
-    fn handle_event(&mut self, generic_event: &AccountEvent) {
+    /// Fold a single event into the dashboard, e.g. while streaming them from the store
+    pub fn handle_event(&mut self, generic_event: &AccountEvent) {
         match generic_event {
             AccountEvent::StocksBought(event) => self.handle_stocks_bought(event.clone()),
+            AccountEvent::StocksSold(event) => self.handle_stocks_sold(event.clone()),
             AccountEvent::PriceObtained(event) => self.handle_price_obtained(event.clone()),
             AccountEvent::DividendPaid(event) => self.handle_dividend_paid(event.clone()),
+            AccountEvent::StockSplit(event) => self.handle_stock_split(event.clone()),
+            AccountEvent::CashDeposited(event) => self.handle_cash_deposited(event.clone()),
+            AccountEvent::CashWithdrawn(event) => self.handle_cash_withdrawn(event.clone()),
+            AccountEvent::FeeCharged(event) => self.handle_fee_charged(event.clone()),
+            AccountEvent::ExchangeRateObtained(event) => {
+                self.handle_exchange_rate_obtained(event.clone())
+            }
+            AccountEvent::AccountOpened(_) => {}
+            // Applied to the events before they are folded, see apply_corrections
+            AccountEvent::EventVoided(_) | AccountEvent::EventAmended(_) => {}
         };
+        self.update_totals();
     }
 
     fn handle_stocks_bought(&mut self, event: StocksBought) {
-        let asset = Asset {
-            identifier: event.identifier.clone(),
+        // Guard against lots in different currencies, which cannot be added up.
+        // The add command rejects them, see check.
+        if self
+            .check(&AccountEvent::StocksBought(event.clone()))
+            .is_err()
+        {
+            return;
+        }
+
+        if self.amount_of(&event.identifier) <= 0.0 {
+            self.number_of_positions += 1.0;
+        }
+
+        self.total_buying_price
+            .upsert(event.price.clone() * event.amount);
+        self.cash.upsert(-event.total());
+        if let Some(fee) = event.fee.clone() {
+            self.total_fees.upsert(fee);
+        }
+
+        // The fee is part of what the stocks cost
+        let position = self
+            .positions
+            .entry(event.identifier.clone())
+            .or_insert_with(|| Position::new(event.currency()));
+        position.buy(Lot {
+            bought_at: event.created_at,
             amount: event.amount,
-            dividends: Amount::zero(event.currency().clone()),
-            value: None,
+            price: event.price_including_fee(),
+        });
+
+        let asset = self
+            .assets
+            .entry(event.identifier.clone())
+            .or_insert_with(|| Asset {
+                dividends: Amount::zero(event.currency()),
+                withheld_tax: Amount::zero(event.currency()),
+                realized_profit_loss: Amount::zero(event.currency()),
+                ..Asset::zero(&event.identifier)
+            });
+        asset.amount += event.amount;
+        asset.cost_basis = position.cost_basis();
+        asset.value = asset.last_price.clone().map(|price| price * asset.amount);
+    }
+
+    fn handle_stocks_sold(&mut self, event: StocksSold) {
+        // Guard against the case where we have not bought any of this stock yet
+        let (Some(asset), Some(position)) = (
+            self.assets.get_mut(&event.identifier),
+            self.positions.get_mut(&event.identifier),
+        ) else {
+            return;
         };
 
-        self.total_buying_price.upsert(event.price * event.amount);
+        let amount_at_hand = asset.amount;
+        let sold_buying_price = position.sell(event.amount, self.cost_basis_method);
+        // The profit or loss is unknown when sold in another currency than bought in
+        let profit_loss = (event.price.currency == sold_buying_price.currency)
+            .then(|| event.total() - sold_buying_price);
+        self.cash.upsert(event.total());
+        if let Some(fee) = event.fee.clone() {
+            self.total_fees.upsert(fee);
+        }
+
+        asset.amount = amount_at_hand - event.amount;
+        asset.cost_basis = position.cost_basis();
+        asset.value = asset.last_price.clone().map(|price| price * asset.amount);
 
-        self.upsert_assets(asset, event.amount);
+        if amount_at_hand > 0.0 && asset.amount <= 0.0 {
+            self.number_of_positions -= 1.0;
+        }
+
+        if let Some(profit_loss) = profit_loss {
+            asset.realized_profit_loss += profit_loss.clone();
+            self.total_realized_profit_loss.upsert(profit_loss);
+        }
     }
 
     fn handle_price_obtained(&mut self, event: PriceObtained) {
         // Guard against the case where we have not bought any of this stock yet
-        if self.assets.get(&event.identifier).is_none() {
+        let Some(asset) = self.assets.get_mut(&event.identifier) else {
             return;
-        }
-
-        // Update the value of the asset
-        let amount_at_hand = self.amount_of(&event.identifier);
-        let asset = Asset {
-            identifier: event.identifier.clone(),
-            amount: amount_at_hand,
-            dividends: Amount::zero(event.currency().clone()),
-            value: Some(event.price.clone() * amount_at_hand),
         };
-        self.assets.insert(event.identifier.clone(), asset);
 
-        // Update the total value
-        self.total_value.upsert(event.price * amount_at_hand);
+        // Update the value of the asset
+        asset.value = Some(event.price.clone() * asset.amount);
+        asset.previous_price = asset.last_price.replace(event.price);
     }
 
     fn handle_dividend_paid(&mut self, event: DividendPaid) {
         if let Some(asset) = self.assets.get_mut(&event.identifier) {
             asset.dividends += event.price.clone() * asset.amount;
+            asset.withheld_tax += event.withheld_tax() * asset.amount;
         }
 
-        let dividend = event.price * self.amount_of(&event.identifier);
-        let new_amount = self.total_dividend.for_currency(&dividend.currency) + dividend;
-        self.total_dividend.upsert(new_amount);
+        let amount = self.amount_of(&event.identifier);
+        let dividend = event.price.clone() * amount;
+        let withheld_tax = event.withheld_tax() * amount;
+        self.cash.upsert(dividend.clone() - withheld_tax.clone());
+        self.total_dividend.upsert(dividend);
+        if event.withheld_tax.is_some() {
+            self.total_withheld_tax.upsert(withheld_tax);
+        }
     }
 
-    fn upsert_assets(&mut self, asset: Asset, amount: f64) {
-        let identifier = asset.identifier.clone();
-        if self.assets.get(&identifier).is_none() {
-            self.number_of_positions += 1.0;
-        }
+    fn handle_stock_split(&mut self, event: StockSplit) {
+        let (Some(asset), Some(position)) = (
+            self.assets.get_mut(&event.identifier),
+            self.positions.get_mut(&event.identifier),
+        ) else {
+            return;
+        };
+
+        // Prices obtained before the split are for the old stocks
+        position.split(event.ratio);
+        asset.amount *= event.ratio.factor();
+        asset.cost_basis = position.cost_basis();
+        asset.last_price = asset
+            .last_price
+            .as_ref()
+            .map(|price| event.ratio.adjust_price(price));
+        asset.previous_price = asset
+            .previous_price
+            .as_ref()
+            .map(|price| event.ratio.adjust_price(price));
+        asset.value = asset.last_price.clone().map(|price| price * asset.amount);
+    }
 
-        let new_asset = if let Some(current_asset) = self.assets.get(&identifier) {
-            Asset {
-                identifier: identifier.clone(),
-                amount: current_asset.amount + amount,
-                dividends: current_asset.dividends.clone(),
-                value: asset.value,
+    fn handle_cash_deposited(&mut self, event: CashDeposited) {
+        self.cash.upsert(event.amount);
+    }
+
+    fn handle_cash_withdrawn(&mut self, event: CashWithdrawn) {
+        self.cash.upsert(-event.amount);
+    }
+
+    fn handle_fee_charged(&mut self, event: FeeCharged) {
+        self.cash.upsert(-event.amount.clone());
+        self.total_fees.upsert(event.amount);
+    }
+
+    fn handle_exchange_rate_obtained(&mut self, event: ExchangeRateObtained) {
+        self.exchange_rates.record(
+            event.currency,
+            event.price.currency,
+            event.created_at,
+            event.price.num,
+        );
+    }
+
+    /// Sum the value of the cash and of all assets for which we know a price,
+    /// and the profit or loss of all assets
+    fn update_totals(&mut self) {
+        let mut total_value = Amounts::zero();
+        let mut total_unrealized_profit_loss = Amounts::zero();
+        let mut total_profit_loss = Amounts::zero();
+
+        for asset in self.assets.values() {
+            if let Some(value) = asset.value.clone() {
+                total_value.upsert(value);
             }
-        } else {
-            asset
-        };
-        self.assets.insert(identifier, new_asset);
+            if let Some(unrealized) = asset.unrealized_profit_loss() {
+                total_unrealized_profit_loss.upsert(unrealized);
+            }
+            total_profit_loss.upsert(
+                asset
+                    .total_profit_loss()
+                    .unwrap_or(asset.realized_profit_loss.clone() + asset.net_dividends()),
+            );
+        }
+
+        for cash in self.cash.amounts.values() {
+            total_value.upsert(cash.clone());
+        }
+
+        self.total_value = total_value;
+        self.total_unrealized_profit_loss = total_unrealized_profit_loss;
+        self.total_profit_loss = total_profit_loss;
+    }
+}
+
+/// An event that does not fit the dashboard it would be folded into
+#[derive(Debug, PartialEq)]
+pub enum InvalidEvent {
+    /// Stocks bought in another currency than the ones already held
+    CurrencyMismatch {
+        identifier: StockIdentifier,
+        held_in: Currency,
+        bought_in: Currency,
+    },
+    /// More stocks sold than held
+    Oversold {
+        identifier: StockIdentifier,
+        held: f64,
+        sold: f64,
+    },
+}
+
+impl Error for InvalidEvent {}
+
+impl Display for InvalidEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidEvent::CurrencyMismatch {
+                identifier,
+                held_in,
+                bought_in,
+            } => write!(
+                f,
+                "{} is held in {}, it cannot be bought in {}",
+                identifier, held_in, bought_in
+            ),
+            InvalidEvent::Oversold {
+                identifier,
+                held,
+                sold,
+            } => write!(
+                f,
+                "{} of {} cannot be sold, only {} are held",
+                sold, identifier, held
+            ),
+        }
     }
 }
 
@@ -184,6 +460,7 @@ mod tests {
     #[test]
     fn test_stocks_bought_adds_totals() {
         let events = vec![
+            AccountEvent::new_cash_deposited(iphone_launched_at(), "42.00 USD".to_string()),
             AccountEvent::new_stocks_bought(
                 iphone_launched_at(),
                 1.0,
@@ -211,6 +488,8 @@ mod tests {
             .and_hms_opt(0, 0, 0)
             .unwrap();
         let events = vec![
+            AccountEvent::new_cash_deposited(iphone_launched_at(), "42.00 USD".to_string()),
+            AccountEvent::new_cash_deposited(iphone_launched_at(), "13.37 EUR".to_string()),
             AccountEvent::new_stocks_bought(
                 iphone_launched_at(),
                 1.0,
@@ -261,6 +540,489 @@ mod tests {
             "133.70 USD".to_string().into()
         );
     }
+
+    #[test]
+    fn test_that_withheld_tax_is_deducted_from_dividend() {
+        let events = vec![
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "13.37 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_dividend_paid(
+                date_time(2020, 1, 1),
+                "1.00 USD".to_string(),
+                "AAPL".to_string(),
+            )
+            .with_withheld_tax("0.15 USD".to_string(), Some("US".to_string()))
+            .unwrap(),
+            AccountEvent::new_dividend_paid(
+                date_time(2020, 4, 1),
+                "1.00 USD".to_string(),
+                "AAPL".to_string(),
+            )
+            .with_withheld_tax("0.15 USD".to_string(), Some("US".to_string()))
+            .unwrap(),
+        ];
+        let dashboard = Dashboard::new(events);
+        let asset = dashboard.assets.get(&"AAPL".into()).unwrap();
+
+        assert_eq!(asset.dividends, "20.00 USD".to_string().into());
+        assert_eq!(asset.withheld_tax, "3.00 USD".to_string().into());
+        assert_eq!(asset.net_dividends(), "17.00 USD".to_string().into());
+        assert_eq!(
+            dashboard.total_dividend,
+            Amounts::new(vec!["20.00 USD".to_string().into()])
+        );
+        assert_eq!(
+            dashboard.total_withheld_tax,
+            Amounts::new(vec!["3.00 USD".to_string().into()])
+        );
+        assert_eq!(
+            dashboard.cash,
+            Amounts::new(vec!["-116.70 USD".to_string().into()])
+        );
+    }
+
+    #[test]
+    fn test_that_stocks_sold_reduces_amount() {
+        let events = vec![
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "10.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_stocks_sold(
+                date_time(2020, 1, 1),
+                4.0,
+                "15.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+        ];
+        let dashboard = Dashboard::new(events);
+
+        let id: StockIdentifier = "AAPL".into();
+        assert_eq!(dashboard.assets.get(&id).unwrap().amount, 6.0);
+        assert_eq!(dashboard.number_of_positions, 1.0);
+    }
+
+    #[test]
+    fn test_that_stocks_sold_realizes_profit_loss() {
+        let events = vec![
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "10.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "20.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_stocks_sold(
+                date_time(2020, 1, 1),
+                5.0,
+                "18.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+        ];
+        let dashboard = Dashboard::new(events);
+
+        let id: StockIdentifier = "AAPL".into();
+        assert_eq!(
+            dashboard.assets.get(&id).unwrap().realized_profit_loss,
+            "40.00 USD".to_string().into()
+        );
+        assert_eq!(
+            dashboard.total_realized_profit_loss,
+            Amounts::new(vec!["40.00 USD".to_string().into()])
+        );
+    }
+
+    #[test]
+    fn test_that_selling_in_another_currency_realizes_no_profit_loss() {
+        let events = vec![
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "10.00 EUR".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_stocks_sold(
+                date_time(2020, 1, 1),
+                5.0,
+                "18.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+        ];
+        let dashboard = Dashboard::new(events);
+
+        let id: StockIdentifier = "AAPL".into();
+        let asset = dashboard.assets.get(&id).unwrap();
+        assert_eq!(asset.amount, 5.0);
+        assert_eq!(asset.realized_profit_loss, "0.00 EUR".to_string().into());
+        assert_eq!(asset.cost_basis, "50.00 EUR".to_string().into());
+        assert_eq!(dashboard.total_realized_profit_loss, Amounts::zero());
+    }
+
+    #[test]
+    fn test_that_buying_in_another_currency_is_rejected() {
+        let dashboard = Dashboard::new(vec![AccountEvent::new_stocks_bought(
+            iphone_launched_at(),
+            10.0,
+            "10.00 USD".to_string(),
+            "AAPL".to_string(),
+        )]);
+        let buy_in_euro = AccountEvent::new_stocks_bought(
+            date_time(2020, 1, 1),
+            5.0,
+            "9.00 EUR".to_string(),
+            "AAPL".to_string(),
+        );
+
+        assert_eq!(
+            dashboard.check(&buy_in_euro).unwrap_err().to_string(),
+            "AAPL is held in USD, it cannot be bought in EUR"
+        );
+
+        // Folding it anyway leaves the position as it was
+        let dashboard = dashboard.replay([buy_in_euro]);
+        let id: StockIdentifier = "AAPL".into();
+        let asset = dashboard.assets.get(&id).unwrap();
+        assert_eq!(asset.amount, 10.0);
+        assert_eq!(asset.cost_basis, "100.00 USD".to_string().into());
+    }
+
+    #[test]
+    fn test_that_selling_more_than_held_is_rejected() {
+        let dashboard = Dashboard::new(vec![AccountEvent::new_stocks_bought(
+            iphone_launched_at(),
+            10.0,
+            "10.00 USD".to_string(),
+            "AAPL".to_string(),
+        )]);
+        let sell = |amount: f64| {
+            AccountEvent::new_stocks_sold(
+                date_time(2020, 1, 1),
+                amount,
+                "12.00 USD".to_string(),
+                "AAPL".to_string(),
+            )
+        };
+
+        assert_eq!(dashboard.check(&sell(10.0)), Ok(()));
+        assert_eq!(
+            dashboard.check(&sell(15.0)).unwrap_err().to_string(),
+            "15 of AAPL cannot be sold, only 10 are held"
+        );
+    }
+
+    #[test]
+    fn test_that_stocks_sold_realizes_profit_loss_by_cost_basis_method() {
+        let events = vec![
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "10.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_stocks_bought(
+                date_time(2019, 1, 1),
+                10.0,
+                "20.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_stocks_sold(
+                date_time(2020, 1, 1),
+                5.0,
+                "18.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+        ];
+
+        let expectations = [
+            (CostBasisMethod::Fifo, "40.00 USD"),
+            (CostBasisMethod::Lifo, "-10.00 USD"),
+            (CostBasisMethod::Average, "15.00 USD"),
+        ];
+        for (method, expected) in expectations {
+            let dashboard = Dashboard::with_cost_basis_method(events.clone(), method);
+            assert_eq!(
+                dashboard.total_realized_profit_loss,
+                Amounts::new(vec![expected.to_string().into()]),
+                "realized P/L using {}",
+                method
+            );
+        }
+    }
+
+    #[test]
+    fn test_that_selling_all_stocks_drops_the_position() {
+        let events = vec![
+            AccountEvent::new_cash_deposited(iphone_launched_at(), "100.00 USD".to_string()),
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "10.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_price_obtained(
+                date_time(2020, 1, 1),
+                "12.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_stocks_sold(
+                date_time(2020, 1, 2),
+                10.0,
+                "12.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+        ];
+        let dashboard = Dashboard::new(events);
+
+        // Only the cash from the sale is left
+        assert_eq!(dashboard.number_of_positions, 0.0);
+        assert_eq!(
+            dashboard.total_value,
+            Amounts::new(vec!["120.00 USD".to_string().into()])
+        );
+    }
+
+    #[test]
+    fn test_that_price_obtained_tracks_unrealized_profit_loss() {
+        let events = vec![
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "10.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_price_obtained(
+                date_time(2020, 1, 1),
+                "12.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_price_obtained(
+                date_time(2020, 1, 2),
+                "15.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+        ];
+        let dashboard = Dashboard::new(events);
+
+        let id: StockIdentifier = "AAPL".into();
+        let asset = dashboard.assets.get(&id).unwrap();
+        assert_eq!(asset.cost_basis, "100.00 USD".to_string().into());
+        assert_eq!(asset.previous_price, Some("12.00 USD".to_string().into()));
+        assert_eq!(asset.last_price, Some("15.00 USD".to_string().into()));
+        assert_eq!(
+            dashboard.total_unrealized_profit_loss,
+            Amounts::new(vec!["50.00 USD".to_string().into()])
+        );
+    }
+
+    #[test]
+    fn test_that_price_in_another_currency_leaves_profit_loss_unknown() {
+        let events = vec![
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "10.00 EUR".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_price_obtained(
+                date_time(2020, 1, 1),
+                "12.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+        ];
+        let dashboard = Dashboard::new(events);
+
+        let id: StockIdentifier = "AAPL".into();
+        let asset = dashboard.assets.get(&id).unwrap();
+        assert_eq!(asset.unrealized_profit_loss(), None);
+        assert_eq!(asset.total_profit_loss(), None);
+        assert_eq!(dashboard.total_unrealized_profit_loss, Amounts::zero());
+    }
+
+    #[test]
+    fn test_that_stock_split_adjusts_amount_and_prices() {
+        let events = vec![
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "100.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_price_obtained(
+                date_time(2020, 1, 1),
+                "120.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_stock_split(
+                date_time(2020, 1, 2),
+                "4:1".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_price_obtained(
+                date_time(2020, 1, 3),
+                "33.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+        ];
+        let dashboard = Dashboard::new(events);
+
+        let id: StockIdentifier = "AAPL".into();
+        let asset = dashboard.assets.get(&id).unwrap();
+        assert_eq!(asset.amount, 40.0);
+        assert_eq!(asset.cost_basis, "1000.00 USD".to_string().into());
+        assert_eq!(asset.previous_price, Some("30.00 USD".to_string().into()));
+        assert_eq!(asset.value, Some("1320.00 USD".to_string().into()));
+    }
+
+    #[test]
+    fn test_that_cash_moves_with_trades_and_dividends() {
+        let events = vec![
+            AccountEvent::new_cash_deposited(iphone_launched_at(), "1000.00 USD".to_string()),
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "50.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_dividend_paid(
+                date_time(2020, 1, 1),
+                "1.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_stocks_sold(
+                date_time(2020, 1, 2),
+                5.0,
+                "60.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_cash_withdrawn(date_time(2020, 1, 3), "100.00 USD".to_string()),
+        ];
+        let dashboard = Dashboard::new(events);
+
+        assert_eq!(
+            dashboard.cash,
+            Amounts::new(vec!["710.00 USD".to_string().into()])
+        );
+    }
+
+    #[test]
+    fn test_that_fees_are_part_of_cost_basis_and_cash() {
+        let events = vec![
+            AccountEvent::new_cash_deposited(iphone_launched_at(), "1000.00 USD".to_string()),
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "50.00 USD".to_string(),
+                "AAPL".to_string(),
+            )
+            .with_fee("10.00 USD".to_string())
+            .unwrap(),
+            AccountEvent::new_stocks_sold(
+                date_time(2020, 1, 2),
+                5.0,
+                "60.00 USD".to_string(),
+                "AAPL".to_string(),
+            )
+            .with_fee("5.00 USD".to_string())
+            .unwrap(),
+            AccountEvent::new_fee_charged(date_time(2020, 1, 3), "2.50 USD".to_string()),
+        ];
+        let dashboard = Dashboard::new(events);
+        let asset = dashboard.assets.get(&"AAPL".into()).unwrap();
+
+        assert_eq!(asset.cost_basis, "255.00 USD".to_string().into());
+        assert_eq!(asset.realized_profit_loss, "40.00 USD".to_string().into());
+        assert_eq!(
+            dashboard.cash,
+            Amounts::new(vec!["782.50 USD".to_string().into()])
+        );
+        assert_eq!(
+            dashboard.total_fees,
+            Amounts::new(vec!["17.50 USD".to_string().into()])
+        );
+    }
+
+    #[test]
+    fn test_that_cash_is_part_of_total_value() {
+        let events = vec![
+            AccountEvent::new_cash_deposited(iphone_launched_at(), "1000.00 USD".to_string()),
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "50.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_price_obtained(
+                date_time(2020, 1, 1),
+                "60.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+        ];
+        let dashboard = Dashboard::new(events);
+
+        assert_eq!(
+            dashboard.total_value,
+            Amounts::new(vec!["1100.00 USD".to_string().into()])
+        );
+    }
+
+    #[test]
+    fn test_consolidate_in_base_currency() {
+        let events = vec![
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                1.0,
+                "42.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                1.0,
+                "13.37 EUR".to_string(),
+                "ASR-AS".to_string(),
+            ),
+            AccountEvent::new_exchange_rate_obtained(
+                date_time(2020, 1, 1),
+                "0.50 EUR".to_string(),
+                "USD".to_string(),
+            ),
+        ];
+        let dashboard = Dashboard::new(events).in_base_currency("EUR".into());
+
+        let consolidated = dashboard
+            .consolidate(&dashboard.total_buying_price)
+            .unwrap();
+        assert_eq!(consolidated.total, "34.37 EUR".to_string().into());
+        assert!(consolidated.is_complete());
+    }
+
+    #[test]
+    fn test_consolidate_without_rate_flags_currency() {
+        let events = vec![AccountEvent::new_stocks_bought(
+            iphone_launched_at(),
+            1.0,
+            "42.00 USD".to_string(),
+            "AAPL".to_string(),
+        )];
+        let dashboard = Dashboard::new(events).in_base_currency("EUR".into());
+
+        let consolidated = dashboard
+            .consolidate(&dashboard.total_buying_price)
+            .unwrap();
+        assert_eq!(consolidated.total, "0.00 EUR".to_string().into());
+        assert_eq!(consolidated.missing_rates, vec!["USD".into()]);
+    }
+
     // TODO: test that stocks bought does not set dividend to zero if it already has a value
 
     fn date_time(year: i32, month: u32, day: u32) -> NaiveDateTime {
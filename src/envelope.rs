@@ -0,0 +1,207 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{date_utils::now, events::AccountEvent};
+
+/// The version of the shape of the events written today
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// An event with the metadata about how and when it was recorded.
+/// The event itself only holds the business date, `created_at`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventEnvelope {
+    /// Unique id, to refer to this event
+    pub id: Uuid,
+    /// The position of the event in the order it was stored in, starting at 1.
+    /// Assigned by the event store when persisting.
+    pub sequence: u64,
+    /// The variant of the event, e.g. StocksBought
+    pub event_type: String,
+    /// The version of the shape of the event when it was stored
+    pub schema_version: u32,
+    /// The time the event was recorded, as opposed to the time it happened
+    pub recorded_at: NaiveDateTime,
+    /// Where the event came from, e.g. the file it was imported from
+    pub source: Option<String>,
+    /// A note from the user
+    pub comment: Option<String>,
+    /// The hash chaining this event to the one stored before it, see hash_chain.
+    /// Assigned by the event store when persisting.
+    pub hash: Option<String>,
+    pub event: AccountEvent,
+}
+
+impl EventEnvelope {
+    /// Wrap the event with a new id, recorded now
+    pub fn new(event: AccountEvent) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            sequence: 0,
+            event_type: event.event_type().to_string(),
+            schema_version: SCHEMA_VERSION,
+            recorded_at: now(),
+            source: None,
+            comment: None,
+            hash: None,
+            event,
+        }
+    }
+
+    pub fn with_source(mut self, source: String) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn with_comment(mut self, comment: Option<String>) -> Self {
+        self.comment = comment;
+        self
+    }
+}
+
+/// Unwrap the events, in the same order
+pub fn into_events(envelopes: Vec<EventEnvelope>) -> Vec<AccountEvent> {
+    envelopes
+        .into_iter()
+        .map(|envelope| envelope.event)
+        .collect()
+}
+
+/// The version of the aggregate the envelopes were read from: the sequence of the last
+/// event stored, 0 when none was. See EventStore::persist_expected.
+pub fn version(envelopes: &[EventEnvelope]) -> u64 {
+    envelopes
+        .iter()
+        .map(|envelope| envelope.sequence)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Apply the corrections: leave out the voided events, replace the amended events by
+/// their latest amendment, and leave out the corrections themselves
+pub fn apply_corrections(envelopes: Vec<EventEnvelope>) -> Vec<EventEnvelope> {
+    let corrections = Corrections::new(&envelopes);
+    envelopes
+        .into_iter()
+        .filter_map(|envelope| corrections.apply(envelope))
+        .collect()
+}
+
+/// The events voided, and the latest amendment of the events amended, to apply to
+/// the events one at a time as they are read
+#[derive(Debug, Default)]
+pub struct Corrections {
+    voided: HashSet<Uuid>,
+    amended: HashMap<Uuid, AccountEvent>,
+}
+
+impl Corrections {
+    /// The corrections among the envelopes. Of several amendments of an event,
+    /// the last one wins.
+    pub fn new(envelopes: &[EventEnvelope]) -> Self {
+        let mut corrections = Self::default();
+        for envelope in envelopes {
+            match &envelope.event {
+                AccountEvent::EventVoided(props) => {
+                    corrections.voided.insert(props.event_id);
+                }
+                AccountEvent::EventAmended(props) => {
+                    corrections
+                        .amended
+                        .insert(props.event_id, *props.event.clone());
+                }
+                _ => {}
+            }
+        }
+        corrections
+    }
+
+    /// The envelope with the corrections applied. None when it was voided, or is a correction.
+    pub fn apply(&self, mut envelope: EventEnvelope) -> Option<EventEnvelope> {
+        if envelope.event.is_correction() || self.voided.contains(&envelope.id) {
+            return None;
+        }
+        if let Some(event) = self.amended.get(&envelope.id) {
+            envelope.event = event.clone();
+        }
+        Some(envelope)
+    }
+}
+
+/// The stored events with their ids, to refer to when voiding or amending one
+pub struct EventLog {
+    pub envelopes: Vec<EventEnvelope>,
+}
+
+/// Wrap all events, marking where they came from
+pub fn wrap_events(events: Vec<AccountEvent>, source: &str) -> Vec<EventEnvelope> {
+    events
+        .into_iter()
+        .map(|event| EventEnvelope::new(event).with_source(source.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::date_utils::fixtures::iphone_launched_at;
+
+    use super::*;
+
+    #[test]
+    fn test_new_envelope() {
+        let event = AccountEvent::new_cash_deposited(iphone_launched_at(), "1.00 USD".to_string());
+        let first = EventEnvelope::new(event.clone());
+        let second = EventEnvelope::new(event);
+
+        assert_ne!(first.id, second.id);
+        assert_eq!(first.event_type, "CashDeposited");
+        assert_eq!(first.schema_version, SCHEMA_VERSION);
+        assert!(first.recorded_at > iphone_launched_at());
+    }
+
+    #[test]
+    fn test_apply_corrections() {
+        let deposit = EventEnvelope::new(AccountEvent::new_cash_deposited(
+            iphone_launched_at(),
+            "1.00 USD".to_string(),
+        ));
+        let buy = EventEnvelope::new(AccountEvent::new_stocks_bought(
+            iphone_launched_at(),
+            10.0,
+            "100.00 USD".to_string(),
+            "AAPL".to_string(),
+        ));
+        let amended_buy = buy
+            .event
+            .clone()
+            .with_price("10.00 USD".to_string())
+            .unwrap();
+        let envelopes = vec![
+            deposit.clone(),
+            buy.clone(),
+            EventEnvelope::new(AccountEvent::new_event_voided(
+                iphone_launched_at(),
+                deposit.id,
+                deposit.event.clone(),
+            )),
+            EventEnvelope::new(AccountEvent::new_event_amended(
+                iphone_launched_at(),
+                buy.id,
+                amended_buy,
+            )),
+        ];
+
+        let corrected = apply_corrections(envelopes);
+
+        assert_eq!(corrected.len(), 1);
+        assert_eq!(corrected[0].id, buy.id);
+        match &corrected[0].event {
+            AccountEvent::StocksBought(event) => {
+                assert_eq!(event.price, crate::value_objects::Amount::from("10.00 USD"))
+            }
+            _ => panic!("Unexpected event type"),
+        }
+    }
+}
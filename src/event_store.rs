@@ -1,18 +1,208 @@
 use std::{error::Error, fmt::Display};
 
-use crate::events::AccountEvent;
+use crate::{envelope::EventEnvelope, snapshot::Snapshot};
 
-mod memory;
+use self::query::EventQuery;
+
+pub mod jsonl;
+pub mod memory;
+pub mod query;
 pub mod sqlite;
 
+/// Open the store at the path: a JSON Lines file when it ends in .jsonl,
+/// a SQLite database otherwise
+pub fn open(path: &str) -> Result<Box<dyn EventStore>, EventStoreError> {
+    if path.ends_with(".jsonl") {
+        Ok(Box::new(jsonl::JsonlEventStore::new(path)))
+    } else {
+        Ok(Box::new(sqlite::SqliteEventStore::new(path)?))
+    }
+}
+
 pub trait EventStore {
-    fn get_events(&self, aggregate_id: &str) -> Result<Vec<AccountEvent>, EventStoreError>;
-    fn persist(&self, aggregate_id: &str, events: &[AccountEvent]) -> Result<(), EventStoreError>;
+    /// Prepare the storage to hold events
+    fn init(&self) -> Result<(), EventStoreError> {
+        Ok(())
+    }
+    /// Bring storage written by an older version up to date.
+    /// Returns the number of events rewritten.
+    fn migrate(&self) -> Result<usize, EventStoreError> {
+        Ok(0)
+    }
+
+    /// The events of the aggregate, sorted by the date they happened and then by sequence
+    fn get_events(&self, aggregate_id: &str) -> Result<Vec<EventEnvelope>, EventStoreError>;
+    /// Append the events. The store assigns their sequence numbers.
+    fn persist(&self, aggregate_id: &str, events: &[EventEnvelope]) -> Result<(), EventStoreError>;
+    /// Append the events, unless events were stored after the expected version: the sequence
+    /// of the last event the caller read, 0 when it read none. Fails with a ConcurrencyConflict then.
+    fn persist_expected(
+        &self,
+        aggregate_id: &str,
+        events: &[EventEnvelope],
+        expected_version: u64,
+    ) -> Result<(), EventStoreError>;
+    /// The ids of all aggregates that have events, sorted
+    fn aggregate_ids(&self) -> Result<Vec<String>, EventStoreError>;
+
+    /// The events of the aggregate stored after the given sequence, sorted like `get_events`.
+    /// None stored is not an error.
+    fn get_events_after(
+        &self,
+        aggregate_id: &str,
+        sequence: u64,
+    ) -> Result<Vec<EventEnvelope>, EventStoreError> {
+        match self.get_events(aggregate_id) {
+            Ok(envelopes) => Ok(envelopes
+                .into_iter()
+                .filter(|envelope| envelope.sequence > sequence)
+                .collect()),
+            Err(EventStoreError::AggregateNotFound(_)) => Ok(vec![]),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The events of the aggregate that match the query, sorted like `get_events`.
+    /// Fails with AggregateNotFound when nothing is stored, like `get_events`;
+    /// none matching is not an error.
+    fn query(
+        &self,
+        aggregate_id: &str,
+        query: &EventQuery,
+    ) -> Result<Vec<EventEnvelope>, EventStoreError> {
+        Ok(self
+            .get_events(aggregate_id)?
+            .into_iter()
+            .filter(|envelope| query.matches(envelope))
+            .collect())
+    }
+
+    /// Call `f` with each event of the aggregate that matches the query, sorted like
+    /// `get_events`, so that it can be folded without holding all events in memory.
+    /// Stops at the first error `f` returns. Fails like `query` when nothing is stored.
+    fn stream_events(
+        &self,
+        aggregate_id: &str,
+        query: &EventQuery,
+        f: &mut dyn FnMut(EventEnvelope) -> Result<(), EventStoreError>,
+    ) -> Result<(), EventStoreError> {
+        for envelope in self.query(aggregate_id, query)? {
+            f(envelope)?;
+        }
+        Ok(())
+    }
+
+    /// The snapshot of the projection of the aggregate, if one was saved
+    fn get_snapshot(
+        &self,
+        aggregate_id: &str,
+        projection: &str,
+    ) -> Result<Option<Snapshot>, EventStoreError>;
+    /// Save the snapshot, replacing the earlier one of the same projection
+    fn save_snapshot(&self, aggregate_id: &str, snapshot: &Snapshot)
+        -> Result<(), EventStoreError>;
+}
+
+/// Fail unless the aggregate is still at the expected version
+fn check_version(
+    aggregate_id: &str,
+    expected_version: Option<u64>,
+    actual: u64,
+) -> Result<(), EventStoreError> {
+    match expected_version {
+        Some(expected) if expected != actual => Err(EventStoreError::ConcurrencyConflict {
+            aggregate_id: aggregate_id.to_string(),
+            expected,
+            actual,
+        }),
+        _ => Ok(()),
+    }
+}
+
+impl<E> EventStore for Box<E>
+where
+    E: EventStore + ?Sized,
+{
+    fn init(&self) -> Result<(), EventStoreError> {
+        (**self).init()
+    }
+
+    fn migrate(&self) -> Result<usize, EventStoreError> {
+        (**self).migrate()
+    }
+
+    fn get_events(&self, aggregate_id: &str) -> Result<Vec<EventEnvelope>, EventStoreError> {
+        (**self).get_events(aggregate_id)
+    }
+
+    fn persist(&self, aggregate_id: &str, events: &[EventEnvelope]) -> Result<(), EventStoreError> {
+        (**self).persist(aggregate_id, events)
+    }
+
+    fn persist_expected(
+        &self,
+        aggregate_id: &str,
+        events: &[EventEnvelope],
+        expected_version: u64,
+    ) -> Result<(), EventStoreError> {
+        (**self).persist_expected(aggregate_id, events, expected_version)
+    }
+
+    fn aggregate_ids(&self) -> Result<Vec<String>, EventStoreError> {
+        (**self).aggregate_ids()
+    }
+
+    fn get_events_after(
+        &self,
+        aggregate_id: &str,
+        sequence: u64,
+    ) -> Result<Vec<EventEnvelope>, EventStoreError> {
+        (**self).get_events_after(aggregate_id, sequence)
+    }
+
+    fn query(
+        &self,
+        aggregate_id: &str,
+        query: &EventQuery,
+    ) -> Result<Vec<EventEnvelope>, EventStoreError> {
+        (**self).query(aggregate_id, query)
+    }
+
+    fn stream_events(
+        &self,
+        aggregate_id: &str,
+        query: &EventQuery,
+        f: &mut dyn FnMut(EventEnvelope) -> Result<(), EventStoreError>,
+    ) -> Result<(), EventStoreError> {
+        (**self).stream_events(aggregate_id, query, f)
+    }
+
+    fn get_snapshot(
+        &self,
+        aggregate_id: &str,
+        projection: &str,
+    ) -> Result<Option<Snapshot>, EventStoreError> {
+        (**self).get_snapshot(aggregate_id, projection)
+    }
+
+    fn save_snapshot(
+        &self,
+        aggregate_id: &str,
+        snapshot: &Snapshot,
+    ) -> Result<(), EventStoreError> {
+        (**self).save_snapshot(aggregate_id, snapshot)
+    }
 }
 
 #[derive(Debug)]
 pub enum EventStoreError {
     AggregateNotFound(String),
+    /// Other events were stored after the version the caller expected
+    ConcurrencyConflict {
+        aggregate_id: String,
+        expected: u64,
+        actual: u64,
+    },
     StorageError(String),
     Unknown,
 }
@@ -24,6 +214,15 @@ impl Display for EventStoreError {
             EventStoreError::AggregateNotFound(id) => {
                 write!(f, "Aggregate with id {} not found", id)
             }
+            EventStoreError::ConcurrencyConflict {
+                aggregate_id,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Aggregate with id {} was changed meanwhile: expected version {}, but it is at {}. Try again",
+                aggregate_id, expected, actual
+            ),
             EventStoreError::StorageError(msg) => write!(f, "Storage error: {}", msg),
             EventStoreError::Unknown => write!(f, "Unknown error"),
         }
@@ -0,0 +1,12 @@
+-- The events table before the envelope was added. Events have no fee or withheld tax.
+CREATE TABLE events (
+    id INTEGER PRIMARY KEY,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    aggregate_id TEXT NOT NULL,
+    event TEXT NOT NULL
+);
+INSERT INTO events (created_at, aggregate_id, event) VALUES
+    ('2007-01-09T09:42:00', 'ber', '{"CashDeposited":{"created_at":"2007-01-09T09:42:00","amount":{"num":"1000.00","currency":"USD"}}}'),
+    ('2007-01-09T09:42:00', 'ber', '{"StocksBought":{"created_at":"2007-01-09T09:42:00","amount":10.0,"price":{"num":"10.00","currency":"USD"},"identifier":{"ticker":"AAPL"}}}'),
+    ('2007-02-09T09:42:00', 'ber', '{"DividendPaid":{"created_at":"2007-02-09T09:42:00","price":{"num":"0.10","currency":"USD"},"identifier":{"ticker":"AAPL"}}}'),
+    ('2007-01-09T09:42:00', 'other', '{"StocksSold":{"created_at":"2007-01-09T09:42:00","amount":1.0,"price":{"num":"12.00","currency":"USD"},"identifier":{"ticker":"AAPL"}}}');
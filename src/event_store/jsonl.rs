@@ -0,0 +1,527 @@
+use std::{
+    collections::BTreeSet,
+    fs::{File, OpenOptions},
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{envelope::EventEnvelope, hash_chain::chain_hash, snapshot::Snapshot};
+
+use super::{check_version, EventStore, EventStoreError};
+
+/// Stores the events in a plain text file, one event per line, in the order they were stored.
+/// Lines are only ever appended, so the file can be kept in version control and diffed.
+///
+/// The snapshots are kept in a file of their own next to it, as they are rewritten
+/// whenever the dashboard changes. They can be left out of version control.
+pub struct JsonlEventStore {
+    path: PathBuf,
+}
+
+/// An event of an aggregate, as written on a line
+#[derive(Serialize)]
+struct Line<'a> {
+    aggregate_id: &'a str,
+    #[serde(flatten)]
+    envelope: &'a EventEnvelope,
+}
+
+/// A line as read
+#[derive(Deserialize)]
+struct StoredLine {
+    aggregate_id: String,
+    id: Uuid,
+    sequence: u64,
+    event_type: String,
+    schema_version: u32,
+    recorded_at: NaiveDateTime,
+    source: Option<String>,
+    comment: Option<String>,
+    hash: Option<String>,
+    event: serde_json::Value,
+}
+
+/// A snapshot of an aggregate, as written on a line of the snapshots file
+#[derive(Serialize, Deserialize)]
+struct SnapshotLine {
+    aggregate_id: String,
+    #[serde(flatten)]
+    snapshot: Snapshot,
+}
+
+impl JsonlEventStore {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: PathBuf::from(path),
+        }
+    }
+
+    fn snapshots_path(&self) -> PathBuf {
+        self.path.with_extension("snapshots.jsonl")
+    }
+
+    /// All events of all aggregates, in the order they were stored
+    fn read_events(&self) -> Result<Vec<(String, EventEnvelope)>, EventStoreError> {
+        let Some(mut file) = open_existing(&self.path)? else {
+            return Ok(vec![]);
+        };
+        file.lock_shared()?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let (events, _) = parse_events(&contents)?;
+        Ok(events)
+    }
+
+    /// Append the lines after the last event, which has to be at the expected version
+    /// of the aggregate when one is given
+    fn append(
+        &self,
+        aggregate_id: &str,
+        events: &[EventEnvelope],
+        expected_version: Option<u64>,
+    ) -> Result<(), EventStoreError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&self.path)?;
+        // Held until the file is closed, so that no other process appends in between
+        file.lock()?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let (stored, length) = parse_events(&contents)?;
+
+        let last = stored
+            .iter()
+            .filter(|(id, _)| id == aggregate_id)
+            .map(|(_, envelope)| envelope)
+            .max_by_key(|envelope| envelope.sequence);
+        let last_sequence = last.map(|envelope| envelope.sequence).unwrap_or(0);
+        let mut previous_hash = last.and_then(|envelope| envelope.hash.clone());
+        check_version(aggregate_id, expected_version, last_sequence)?;
+
+        let mut lines = String::new();
+        // The last line was stored without its newline when writing it was cut off
+        if length > 0 && !contents[..length].ends_with('\n') {
+            lines.push('\n');
+        }
+        for (index, envelope) in events.iter().enumerate() {
+            let mut envelope = EventEnvelope {
+                sequence: last_sequence + index as u64 + 1,
+                ..envelope.clone()
+            };
+            let hash = chain_hash(&envelope, previous_hash.as_deref());
+            envelope.hash = Some(hash.clone());
+            lines.push_str(&serde_json::to_string(&Line {
+                aggregate_id,
+                envelope: &envelope,
+            })?);
+            lines.push('\n');
+            previous_hash = Some(hash);
+        }
+
+        // Leave out what is left of a line that was cut off
+        file.set_len(length as u64)?;
+        file.seek(SeekFrom::Start(length as u64))?;
+        file.write_all(lines.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    fn read_snapshots(&self) -> Result<Vec<SnapshotLine>, EventStoreError> {
+        let Some(mut file) = open_existing(&self.snapshots_path())? else {
+            return Ok(vec![]);
+        };
+        file.lock_shared()?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(parse_snapshots(&contents))
+    }
+}
+
+impl EventStore for JsonlEventStore {
+    fn init(&self) -> Result<(), EventStoreError> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        Ok(())
+    }
+
+    fn get_events(&self, aggregate_id: &str) -> Result<Vec<EventEnvelope>, EventStoreError> {
+        let mut events: Vec<EventEnvelope> = self
+            .read_events()?
+            .into_iter()
+            .filter(|(id, _)| id == aggregate_id)
+            .map(|(_, envelope)| envelope)
+            .collect();
+
+        if events.is_empty() {
+            Err(EventStoreError::AggregateNotFound(aggregate_id.to_string()))
+        } else {
+            events.sort_by_key(|envelope| (envelope.event.created_at(), envelope.sequence));
+            Ok(events)
+        }
+    }
+
+    fn persist(&self, aggregate_id: &str, events: &[EventEnvelope]) -> Result<(), EventStoreError> {
+        self.append(aggregate_id, events, None)
+    }
+
+    fn persist_expected(
+        &self,
+        aggregate_id: &str,
+        events: &[EventEnvelope],
+        expected_version: u64,
+    ) -> Result<(), EventStoreError> {
+        self.append(aggregate_id, events, Some(expected_version))
+    }
+
+    fn aggregate_ids(&self) -> Result<Vec<String>, EventStoreError> {
+        let aggregate_ids: BTreeSet<String> = self
+            .read_events()?
+            .into_iter()
+            .map(|(aggregate_id, _)| aggregate_id)
+            .collect();
+        Ok(aggregate_ids.into_iter().collect())
+    }
+
+    fn get_snapshot(
+        &self,
+        aggregate_id: &str,
+        projection: &str,
+    ) -> Result<Option<Snapshot>, EventStoreError> {
+        Ok(self
+            .read_snapshots()?
+            .into_iter()
+            .find(|line| {
+                line.aggregate_id == aggregate_id && line.snapshot.projection == projection
+            })
+            .map(|line| line.snapshot))
+    }
+
+    fn save_snapshot(
+        &self,
+        aggregate_id: &str,
+        snapshot: &Snapshot,
+    ) -> Result<(), EventStoreError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(self.snapshots_path())?;
+        file.lock()?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        let mut lines = String::new();
+        let others = parse_snapshots(&contents).into_iter().filter(|line| {
+            line.aggregate_id != aggregate_id || line.snapshot.projection != snapshot.projection
+        });
+        let saved = SnapshotLine {
+            aggregate_id: aggregate_id.to_string(),
+            snapshot: snapshot.clone(),
+        };
+        for line in others.chain([saved]) {
+            lines.push_str(&serde_json::to_string(&line)?);
+            lines.push('\n');
+        }
+
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(lines.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// The file, or None when nothing was stored yet
+fn open_existing(path: &Path) -> Result<Option<File>, EventStoreError> {
+    match File::open(path) {
+        Ok(file) => Ok(Some(file)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// The events on the lines, and the length of the contents that holds them. A last line
+/// that cannot be read and has no newline was cut off while writing it, and is left out.
+fn parse_events(contents: &str) -> Result<(Vec<(String, EventEnvelope)>, usize), EventStoreError> {
+    let mut events = vec![];
+    let mut length = 0;
+    for (number, line) in contents.split_inclusive('\n').enumerate() {
+        if !line.trim().is_empty() {
+            match parse_event(line) {
+                Ok(event) => events.push(event),
+                Err(_) if !line.ends_with('\n') => break,
+                Err(err) => {
+                    return Err(EventStoreError::StorageError(format!(
+                        "line {} cannot be read: {}",
+                        number + 1,
+                        err
+                    )))
+                }
+            }
+        }
+        length += line.len();
+    }
+    Ok((events, length))
+}
+
+fn parse_event(line: &str) -> Result<(String, EventEnvelope), serde_json::Error> {
+    let stored: StoredLine = serde_json::from_str(line)?;
+    let envelope = EventEnvelope {
+        id: stored.id,
+        sequence: stored.sequence,
+        event_type: stored.event_type,
+        schema_version: stored.schema_version,
+        recorded_at: stored.recorded_at,
+        source: stored.source,
+        comment: stored.comment,
+        hash: stored.hash,
+        event: serde_json::from_value(stored.event)?,
+    };
+    Ok((stored.aggregate_id, envelope))
+}
+
+/// The snapshots on the lines. They can be folded again, so lines that cannot be read are left out.
+fn parse_snapshots(contents: &str) -> Vec<SnapshotLine> {
+    contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+impl From<std::io::Error> for EventStoreError {
+    fn from(err: std::io::Error) -> Self {
+        EventStoreError::StorageError(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use crate::{
+        date_utils::fixtures::iphone_launched_at, events::AccountEvent, hash_chain::verify_chain,
+    };
+
+    use super::*;
+
+    fn deposit(amount: &str) -> EventEnvelope {
+        EventEnvelope::new(AccountEvent::new_cash_deposited(
+            iphone_launched_at(),
+            amount.to_string(),
+        ))
+    }
+
+    #[test]
+    fn test_jsonl_persist_writes_a_line_per_event() {
+        let (dir, event_store) = setup_file();
+        event_store
+            .persist("ber", &[deposit("1.00 USD"), deposit("2.00 USD")])
+            .unwrap();
+        event_store
+            .persist("pension", &[deposit("3.00 USD")])
+            .unwrap();
+
+        let contents = fs::read_to_string(&event_store.path).unwrap();
+        assert_eq!(contents.lines().count(), 3);
+        assert!(contents.ends_with('\n'));
+        assert!(contents.starts_with(r#"{"aggregate_id":"ber","#));
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_jsonl_get_events() {
+        let (dir, event_store) = setup_file();
+        let events = vec![deposit("1.00 USD"), deposit("2.00 USD")];
+        event_store.persist("ber", &events).unwrap();
+        event_store
+            .persist("pension", &[deposit("3.00 USD")])
+            .unwrap();
+
+        let stored = event_store.get_events("ber").unwrap();
+
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[0].id, events[0].id);
+        assert_eq!(stored[1].sequence, 2);
+        assert_eq!(stored[1].id, events[1].id);
+        assert_eq!(
+            event_store.aggregate_ids().unwrap(),
+            vec!["ber".to_string(), "pension".to_string()]
+        );
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_jsonl_get_events_not_found() {
+        let (dir, event_store) = setup_file();
+
+        let result = event_store.get_events("ber");
+
+        assert!(matches!(result, Err(EventStoreError::AggregateNotFound(_))));
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_jsonl_persist_chains_events() {
+        let (dir, event_store) = setup_file();
+        event_store.persist("ber", &[deposit("1.00 USD")]).unwrap();
+        event_store
+            .persist("ber", &[deposit("2.00 USD"), deposit("3.00 USD")])
+            .unwrap();
+
+        let head = verify_chain(event_store.get_events("ber").unwrap()).unwrap();
+        assert_eq!(head.events, 3);
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_jsonl_persist_expected() {
+        let (dir, event_store) = setup_file();
+        event_store
+            .persist_expected("ber", &[deposit("1.00 USD")], 0)
+            .unwrap();
+        event_store
+            .persist("pension", &[deposit("2.00 USD")])
+            .unwrap();
+        event_store
+            .persist_expected("ber", &[deposit("3.00 USD")], 1)
+            .unwrap();
+
+        let result = event_store.persist_expected("ber", &[deposit("4.00 USD")], 1);
+
+        match result {
+            Err(EventStoreError::ConcurrencyConflict {
+                expected, actual, ..
+            }) => assert_eq!((expected, actual), (1, 2)),
+            _ => panic!("Expected a concurrency conflict"),
+        }
+        assert_eq!(event_store.get_events("ber").unwrap().len(), 2);
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_jsonl_recovers_from_a_cut_off_line() {
+        let (dir, event_store) = setup_file();
+        event_store
+            .persist("ber", &[deposit("1.00 USD"), deposit("2.00 USD")])
+            .unwrap();
+        let contents = fs::read_to_string(&event_store.path).unwrap();
+        fs::write(&event_store.path, &contents[..contents.len() - 20]).unwrap();
+
+        assert_eq!(event_store.get_events("ber").unwrap().len(), 1);
+
+        event_store.persist("ber", &[deposit("3.00 USD")]).unwrap();
+        let stored = event_store.get_events("ber").unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[1].sequence, 2);
+        assert!(verify_chain(stored).is_ok());
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_jsonl_keeps_a_last_line_without_newline() {
+        let (dir, event_store) = setup_file();
+        event_store.persist("ber", &[deposit("1.00 USD")]).unwrap();
+        let contents = fs::read_to_string(&event_store.path).unwrap();
+        fs::write(&event_store.path, contents.trim_end()).unwrap();
+
+        event_store.persist("ber", &[deposit("2.00 USD")]).unwrap();
+
+        assert_eq!(event_store.get_events("ber").unwrap().len(), 2);
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_jsonl_unreadable_line_is_an_error() {
+        let (dir, event_store) = setup_file();
+        event_store.persist("ber", &[deposit("1.00 USD")]).unwrap();
+        let contents = fs::read_to_string(&event_store.path).unwrap();
+        fs::write(&event_store.path, format!("not an event\n{}", contents)).unwrap();
+
+        let result = event_store.get_events("ber");
+
+        match result {
+            Err(EventStoreError::StorageError(msg)) => assert!(msg.starts_with("line 1 ")),
+            _ => panic!("Expected a storage error"),
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_jsonl_reads_events_stored_without_the_fee() {
+        let (dir, event_store) = setup_file();
+        let line = r#"{"aggregate_id":"ber","id":"8d8e4d1c-9ef6-4f0e-8c3b-2f61c0b3e0a1","sequence":1,"event_type":"StocksBought","schema_version":1,"recorded_at":"2023-01-01T00:00:00","source":null,"comment":null,"hash":null,"event":{"StocksBought":{"created_at":"2007-01-09T09:42:00","amount":10.0,"price":{"num":"10.00","currency":"USD"},"identifier":{"ticker":"AAPL"}}}}"#;
+        fs::write(&event_store.path, format!("{}\n", line)).unwrap();
+
+        let stored = event_store.get_events("ber").unwrap();
+
+        match &stored[0].event {
+            AccountEvent::StocksBought(event) => assert_eq!(event.fee, None),
+            _ => panic!("Unexpected event type"),
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_jsonl_save_snapshot_replaces_the_previous() {
+        let (dir, event_store) = setup_file();
+        let snapshot = Snapshot {
+            projection: "dashboard fifo".to_string(),
+            version: "1".to_string(),
+            sequence: 1,
+            created_at: iphone_launched_at(),
+            state: "{}".to_string(),
+        };
+        let later = Snapshot {
+            sequence: 2,
+            ..snapshot.clone()
+        };
+
+        event_store.save_snapshot("ber", &snapshot).unwrap();
+        event_store.save_snapshot("pension", &snapshot).unwrap();
+        event_store.save_snapshot("ber", &later).unwrap();
+
+        assert_eq!(
+            event_store.get_snapshot("ber", "dashboard fifo").unwrap(),
+            Some(later)
+        );
+        assert_eq!(
+            event_store
+                .get_snapshot("pension", "dashboard fifo")
+                .unwrap(),
+            Some(snapshot)
+        );
+        assert_eq!(
+            event_store.get_snapshot("ber", "dashboard lifo").unwrap(),
+            None
+        );
+        assert_eq!(fs::read_to_string(&event_store.path).unwrap(), "");
+
+        dir.close().unwrap();
+    }
+
+    fn setup_file() -> (TempDir, JsonlEventStore) {
+        let temp_dir = tempfile::tempdir().expect("Failed to create tmp directory");
+        let path = temp_dir.path().join("events.jsonl");
+        let event_store =
+            JsonlEventStore::new(path.to_str().expect("Failed to convert path to string"));
+        event_store.init().unwrap();
+        (temp_dir, event_store)
+    }
+}
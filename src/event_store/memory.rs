@@ -1,48 +1,120 @@
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
-use crate::events::AccountEvent;
+use crate::{envelope::EventEnvelope, hash_chain::chain_hash, snapshot::Snapshot};
 
-use super::{EventStore, EventStoreError};
+use super::{check_version, EventStore, EventStoreError};
 
 #[derive(Default)]
 pub struct MemoryEventStore {
-    events: Arc<Mutex<HashMap<String, Vec<AccountEvent>>>>,
+    events: Arc<Mutex<HashMap<String, Vec<EventEnvelope>>>>,
+    /// Per aggregate and projection
+    snapshots: Arc<Mutex<HashMap<(String, String), Snapshot>>>,
+}
+
+impl MemoryEventStore {
+    /// Append the events, when the aggregate is at the expected version if one is given
+    fn append(
+        &self,
+        aggregate_id: &str,
+        events: &[EventEnvelope],
+        expected_version: Option<u64>,
+    ) -> Result<(), EventStoreError> {
+        let mut events_map = self.events.lock().unwrap();
+        let version = events_map
+            .get(aggregate_id)
+            .map_or(0, |events| events.len());
+        check_version(aggregate_id, expected_version, version as u64)?;
+        let aggregate_events = events_map.entry(aggregate_id.to_string()).or_default();
+        for envelope in events {
+            let mut envelope = EventEnvelope {
+                sequence: aggregate_events.len() as u64 + 1,
+                ..envelope.clone()
+            };
+            let previous_hash = aggregate_events
+                .last()
+                .and_then(|last| last.hash.as_deref());
+            envelope.hash = Some(chain_hash(&envelope, previous_hash));
+            aggregate_events.push(envelope);
+        }
+        Ok(())
+    }
 }
 
 impl EventStore for MemoryEventStore {
-    fn get_events(&self, aggregate_id: &str) -> Result<Vec<AccountEvent>, EventStoreError> {
+    fn get_events(&self, aggregate_id: &str) -> Result<Vec<EventEnvelope>, EventStoreError> {
         let events_map = self.events.lock().unwrap();
         if let Some(events) = events_map.get(aggregate_id) {
-            Ok(events.clone())
+            let mut events = events.clone();
+            events.sort_by_key(|envelope| (envelope.event.created_at(), envelope.sequence));
+            Ok(events)
         } else {
             Err(EventStoreError::AggregateNotFound(aggregate_id.to_string()))
         }
     }
 
-    fn persist(&self, aggregate_id: &str, events: &[AccountEvent]) -> Result<(), EventStoreError> {
-        let mut events_map = self.events.lock().unwrap();
-        let aggregate_events = events_map.entry(aggregate_id.to_string()).or_default();
-        aggregate_events.extend_from_slice(events);
+    fn persist(&self, aggregate_id: &str, events: &[EventEnvelope]) -> Result<(), EventStoreError> {
+        self.append(aggregate_id, events, None)
+    }
+
+    fn persist_expected(
+        &self,
+        aggregate_id: &str,
+        events: &[EventEnvelope],
+        expected_version: u64,
+    ) -> Result<(), EventStoreError> {
+        self.append(aggregate_id, events, Some(expected_version))
+    }
+
+    fn aggregate_ids(&self) -> Result<Vec<String>, EventStoreError> {
+        let events_map = self.events.lock().unwrap();
+        let mut aggregate_ids = events_map.keys().cloned().collect::<Vec<String>>();
+        aggregate_ids.sort();
+        Ok(aggregate_ids)
+    }
+
+    fn get_snapshot(
+        &self,
+        aggregate_id: &str,
+        projection: &str,
+    ) -> Result<Option<Snapshot>, EventStoreError> {
+        let snapshots = self.snapshots.lock().unwrap();
+        Ok(snapshots
+            .get(&(aggregate_id.to_string(), projection.to_string()))
+            .cloned())
+    }
+
+    fn save_snapshot(
+        &self,
+        aggregate_id: &str,
+        snapshot: &Snapshot,
+    ) -> Result<(), EventStoreError> {
+        let mut snapshots = self.snapshots.lock().unwrap();
+        snapshots.insert(
+            (aggregate_id.to_string(), snapshot.projection.clone()),
+            snapshot.clone(),
+        );
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{date_utils::fixtures::iphone_launched_at, events::AccountEvent};
+    use crate::{
+        date_utils::fixtures::iphone_launched_at, envelope::EventEnvelope, events::AccountEvent,
+    };
 
     use super::*;
 
     #[test]
     fn test_memory_persist() {
         let event_store = MemoryEventStore::default();
-        let events = vec![AccountEvent::new_stocks_bought(
+        let events = vec![EventEnvelope::new(AccountEvent::new_stocks_bought(
             iphone_launched_at(),
             10.0,
             "100.00 USD".to_string(),
             "AAPL".to_string(),
-        )];
+        ))];
         event_store.persist("123", &events).unwrap();
         let events = event_store.get_events("123").unwrap();
         assert_eq!(events.len(), 1);
@@ -51,21 +123,94 @@ mod tests {
     #[test]
     fn test_memory_get_events() {
         let event_store = MemoryEventStore::default();
-        let events = vec![AccountEvent::new_stocks_bought(
+        let events = vec![EventEnvelope::new(AccountEvent::new_stocks_bought(
             iphone_launched_at(),
             10.0,
             "100.00 USD".to_string(),
             "AAPL".to_string(),
-        )];
+        ))];
         event_store.persist("123", &events).unwrap();
         let events = event_store.get_events("123").unwrap();
         assert_eq!(events.len(), 1);
     }
 
+    #[test]
+    fn test_memory_persist_assigns_sequence() {
+        let event_store = MemoryEventStore::default();
+        let event = AccountEvent::new_cash_deposited(iphone_launched_at(), "1.00 USD".to_string());
+        event_store
+            .persist("123", &[EventEnvelope::new(event.clone())])
+            .unwrap();
+        event_store
+            .persist("123", &[EventEnvelope::new(event)])
+            .unwrap();
+
+        let sequences = event_store
+            .get_events("123")
+            .unwrap()
+            .iter()
+            .map(|envelope| envelope.sequence)
+            .collect::<Vec<u64>>();
+        assert_eq!(sequences, vec![1, 2]);
+    }
+
     #[test]
     fn test_memory_get_events_not_found() {
         let event_store = MemoryEventStore::default();
         let events = event_store.get_events("123");
         assert!(events.is_err());
     }
+
+    #[test]
+    fn test_memory_aggregate_ids() {
+        let event_store = MemoryEventStore::default();
+        let event = AccountEvent::new_cash_deposited(iphone_launched_at(), "1.00 USD".to_string());
+        event_store
+            .persist("pension", &[EventEnvelope::new(event.clone())])
+            .unwrap();
+        event_store
+            .persist("personal", &[EventEnvelope::new(event)])
+            .unwrap();
+
+        assert_eq!(
+            event_store.aggregate_ids().unwrap(),
+            vec!["pension", "personal"]
+        );
+    }
+
+    #[test]
+    fn test_memory_persist_expected() {
+        let event_store = MemoryEventStore::default();
+        let event = AccountEvent::new_cash_deposited(iphone_launched_at(), "1.00 USD".to_string());
+        event_store
+            .persist_expected("123", &[EventEnvelope::new(event.clone())], 0)
+            .unwrap();
+        event_store
+            .persist_expected("123", &[EventEnvelope::new(event.clone())], 1)
+            .unwrap();
+
+        let result = event_store.persist_expected("123", &[EventEnvelope::new(event)], 1);
+
+        match result {
+            Err(EventStoreError::ConcurrencyConflict {
+                expected, actual, ..
+            }) => assert_eq!((expected, actual), (1, 2)),
+            _ => panic!("Expected a concurrency conflict"),
+        }
+        assert_eq!(event_store.get_events("123").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_memory_persist_expected_of_new_aggregate() {
+        let event_store = MemoryEventStore::default();
+        let event = AccountEvent::new_cash_deposited(iphone_launched_at(), "1.00 USD".to_string());
+
+        let result = event_store.persist_expected("123", &[EventEnvelope::new(event)], 1);
+
+        assert!(matches!(
+            result,
+            Err(EventStoreError::ConcurrencyConflict { .. })
+        ));
+        assert!(event_store.aggregate_ids().unwrap().is_empty());
+    }
 }
@@ -0,0 +1,144 @@
+use chrono::NaiveDate;
+
+use crate::{envelope::EventEnvelope, value_objects::StockIdentifier};
+
+/// Which events of an aggregate to read. Without any filter, all events are read.
+///
+/// Corrections are always read, whatever the filters, so that they can be applied
+/// to the events that are. See apply_corrections.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventQuery {
+    /// Only events that happened on or after this date
+    pub from: Option<NaiveDate>,
+    /// Only events that happened on or before this date
+    pub until: Option<NaiveDate>,
+    /// Only events of these types, e.g. StocksBought. Empty means all types.
+    pub event_types: Vec<String>,
+    /// Only events about this stock
+    pub identifier: Option<StockIdentifier>,
+}
+
+impl EventQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from(mut self, from: NaiveDate) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    pub fn until(mut self, until: NaiveDate) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    pub fn with_event_types(mut self, event_types: &[&str]) -> Self {
+        self.event_types = event_types.iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    pub fn with_identifier(mut self, identifier: StockIdentifier) -> Self {
+        self.identifier = Some(identifier);
+        self
+    }
+
+    /// Whether the event is read by the query
+    pub fn matches(&self, envelope: &EventEnvelope) -> bool {
+        let event = &envelope.event;
+        let date = event.created_at().date();
+
+        event.is_correction()
+            || (self.from.is_none_or(|from| date >= from)
+                && self.until.is_none_or(|until| date <= until)
+                && (self.event_types.is_empty() || self.event_types.contains(&envelope.event_type))
+                && self
+                    .identifier
+                    .as_ref()
+                    .is_none_or(|identifier| event.identifier() == Some(identifier)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::{date_utils::fixtures::iphone_launched_at, events::AccountEvent};
+
+    use super::*;
+
+    fn bought(ticker: &str) -> EventEnvelope {
+        EventEnvelope::new(AccountEvent::new_stocks_bought(
+            iphone_launched_at(),
+            10.0,
+            "100.00 USD".to_string(),
+            ticker.to_string(),
+        ))
+    }
+
+    #[test]
+    fn test_query_without_filters_matches_all() {
+        let deposit = EventEnvelope::new(AccountEvent::new_cash_deposited(
+            iphone_launched_at(),
+            "1.00 USD".to_string(),
+        ));
+
+        assert!(EventQuery::new().matches(&bought("AAPL")));
+        assert!(EventQuery::new().matches(&deposit));
+    }
+
+    #[test]
+    fn test_query_by_date_range() {
+        let day = iphone_launched_at().date();
+
+        assert!(EventQuery::new()
+            .from(day)
+            .until(day)
+            .matches(&bought("AAPL")));
+        assert!(!EventQuery::new()
+            .from(day + Duration::days(1))
+            .matches(&bought("AAPL")));
+        assert!(!EventQuery::new()
+            .until(day - Duration::days(1))
+            .matches(&bought("AAPL")));
+    }
+
+    #[test]
+    fn test_query_by_event_type() {
+        let query = EventQuery::new().with_event_types(&["StocksSold", "DividendPaid"]);
+
+        assert!(!query.matches(&bought("AAPL")));
+        assert!(EventQuery::new()
+            .with_event_types(&["StocksBought"])
+            .matches(&bought("AAPL")));
+    }
+
+    #[test]
+    fn test_query_by_identifier() {
+        let query = EventQuery::new().with_identifier(StockIdentifier::from("AAPL"));
+        let deposit = EventEnvelope::new(AccountEvent::new_cash_deposited(
+            iphone_launched_at(),
+            "1.00 USD".to_string(),
+        ));
+
+        assert!(query.matches(&bought("AAPL")));
+        assert!(!query.matches(&bought("MSFT")));
+        assert!(!query.matches(&deposit));
+    }
+
+    #[test]
+    fn test_query_always_matches_corrections() {
+        let buy = bought("MSFT");
+        let void = EventEnvelope::new(AccountEvent::new_event_voided(
+            iphone_launched_at() + Duration::days(10),
+            buy.id,
+            buy.event,
+        ));
+        let query = EventQuery::new()
+            .until(iphone_launched_at().date())
+            .with_event_types(&["StocksBought"])
+            .with_identifier(StockIdentifier::from("AAPL"));
+
+        assert!(query.matches(&void));
+    }
+}
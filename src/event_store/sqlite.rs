@@ -1,72 +1,491 @@
-use crate::{event_store::EventStoreError, events::AccountEvent};
-use rusqlite::{params, Connection};
-use std::rc::Rc;
+use crate::{
+    envelope::EventEnvelope, event_store::EventStoreError, hash_chain::chain_hash,
+    snapshot::Snapshot,
+};
+use chrono::NaiveDateTime;
+use rusqlite::{
+    params, params_from_iter, types::ToSql, Connection, OptionalExtension, Row, Transaction,
+    TransactionBehavior,
+};
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+use uuid::Uuid;
 
-use super::EventStore;
+use super::{check_version, query::EventQuery, EventStore};
 
 pub struct SqliteEventStore {
     db: Rc<Connection>,
+    /// Whether the tables need `migrate` before events can be read. Checked when the
+    /// database is opened, and again after `init` and `migrate` change the tables.
+    outdated_layout: Cell<bool>,
 }
 
 impl SqliteEventStore {
     pub fn new(path: &str) -> Result<Self, EventStoreError> {
         let db = Connection::open(path)?;
-        Ok(Self { db: Rc::new(db) })
+        let store = Self {
+            db: Rc::new(db),
+            outdated_layout: Cell::new(false),
+        };
+        store.outdated_layout.set(store.has_outdated_layout()?);
+        Ok(store)
     }
 
-    pub fn init(&self) -> Result<(), EventStoreError> {
+    fn columns(&self) -> Result<Vec<String>, EventStoreError> {
+        let columns = self
+            .db
+            .prepare("SELECT name FROM pragma_table_info('events')")?
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(columns)
+    }
+
+    /// The events table from before the envelope only had id, created_at, aggregate_id and event
+    fn has_legacy_layout(&self) -> Result<bool, EventStoreError> {
+        let columns = self.columns()?;
+        Ok(!columns.is_empty() && !columns.iter().any(|column| column == "uuid"))
+    }
+
+    /// Any layout from before the hash chain or the snapshots, which `migrate` brings up to date
+    fn has_outdated_layout(&self) -> Result<bool, EventStoreError> {
+        let columns = self.columns()?;
+        let has_snapshots: bool = self.db.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'snapshots')",
+            [],
+            |row| row.get(0),
+        )?;
+        let has_hash = columns.iter().any(|column| column == "hash");
+        Ok(!columns.is_empty() && (!has_hash || !has_snapshots))
+    }
+
+    fn move_legacy_events(&self) -> Result<(), EventStoreError> {
+        self.db
+            .execute("ALTER TABLE events RENAME TO legacy_events", [])?;
+        self.init()?;
+
+        let rows: Vec<(String, String)> = self
+            .db
+            .prepare("SELECT aggregate_id, event FROM legacy_events ORDER BY id ASC")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+
+        let mut sequences: HashMap<String, u64> = HashMap::new();
+        for (aggregate_id, event) in rows {
+            // Keep the event as it was stored, its shape did not change
+            let envelope = EventEnvelope::new(serde_json::from_str(&event)?)
+                .with_source("migrate".to_string());
+            let sequence = sequences.entry(aggregate_id.clone()).or_default();
+            *sequence += 1;
+
+            self.db.execute(
+                "INSERT INTO events
+                (uuid, aggregate_id, sequence, event_type, schema_version, created_at, recorded_at, source, comment, event)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, NULL, ?)",
+                params![
+                    envelope.id.to_string(),
+                    &aggregate_id,
+                    *sequence,
+                    envelope.event_type,
+                    envelope.schema_version,
+                    envelope.event.created_at(),
+                    envelope.recorded_at,
+                    envelope.source,
+                    &event
+                ],
+            )?;
+        }
+
+        self.db.execute("DROP TABLE legacy_events", [])?;
+        Ok(())
+    }
+
+    /// Insert the events after the last one of the aggregate, which has to be at the
+    /// expected version when one is given. All events are inserted, or none are.
+    fn insert(
+        &self,
+        aggregate_id: &str,
+        events: &[EventEnvelope],
+        expected_version: Option<u64>,
+    ) -> Result<(), EventStoreError> {
+        // Immediate, so that other processes cannot write between reading the last
+        // event and inserting after it. Rolled back when dropped before the commit.
+        let tx = Transaction::new_unchecked(&self.db, TransactionBehavior::Immediate)?;
+        let last: Option<(u64, Option<String>)> = self
+            .db
+            .query_row(
+                "SELECT sequence, hash FROM events WHERE aggregate_id = ?
+                ORDER BY sequence DESC LIMIT 1",
+                [&aggregate_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let (last_sequence, mut previous_hash) = last.unwrap_or((0, None));
+        check_version(aggregate_id, expected_version, last_sequence)?;
+
+        let mut stmt = self.db.prepare(
+            "INSERT INTO events
+            (uuid, aggregate_id, sequence, event_type, schema_version, created_at, recorded_at, source, comment, event, hash)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )?;
+        for (index, envelope) in events.iter().enumerate() {
+            let envelope = EventEnvelope {
+                sequence: last_sequence + index as u64 + 1,
+                ..envelope.clone()
+            };
+            let hash = chain_hash(&envelope, previous_hash.as_deref());
+            let event = serde_json::to_string(&envelope.event)?;
+
+            stmt.execute(params![
+                envelope.id.to_string(),
+                &aggregate_id,
+                envelope.sequence,
+                envelope.event_type,
+                envelope.schema_version,
+                envelope.event.created_at(),
+                envelope.recorded_at,
+                envelope.source,
+                envelope.comment,
+                &event,
+                &hash
+            ])?;
+            previous_hash = Some(hash);
+        }
+        drop(stmt);
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The events of the aggregate stored after the sequence that match the query,
+    /// sorted by the date they happened
+    fn query_events(
+        &self,
+        aggregate_id: &str,
+        after: u64,
+        query: &EventQuery,
+    ) -> Result<Vec<EventEnvelope>, EventStoreError> {
+        let mut events = vec![];
+        self.each_event(aggregate_id, after, query, &mut |envelope| {
+            events.push(envelope);
+            Ok(())
+        })?;
+        Ok(events)
+    }
+
+    /// Call `f` with each event like `query_events` returns them, one row at a time.
+    /// Returns whether there were any.
+    fn each_event(
+        &self,
+        aggregate_id: &str,
+        after: u64,
+        query: &EventQuery,
+        f: &mut dyn FnMut(EventEnvelope) -> Result<(), EventStoreError>,
+    ) -> Result<bool, EventStoreError> {
+        if self.outdated_layout.get() {
+            return Err(EventStoreError::StorageError(
+                "the database was written by an older version, run `bullboard migrate` first"
+                    .to_string(),
+            ));
+        }
+        let (filters, mut values) = query_filters(query);
+        let mut stmt = self.db.prepare(&format!(
+            "SELECT uuid, sequence, event_type, schema_version, recorded_at, source, comment, event, hash, created_at
+            FROM events WHERE aggregate_id = ? AND sequence > ?
+            AND (event_type IN ('EventVoided', 'EventAmended') OR ({}))
+            ORDER BY created_at ASC, sequence ASC",
+            filters
+        ))?;
+        values.insert(0, Box::new(aggregate_id.to_string()));
+        values.insert(1, Box::new(after));
+
+        let mut rows = stmt.query(params_from_iter(values))?;
+        let mut any = false;
+        while let Some(row) = rows.next()? {
+            let envelope = envelope_from_row(row)?;
+            // The events are sorted on created_at, which the hash chain does not cover
+            let created_at: NaiveDateTime = row.get(9)?;
+            if created_at != envelope.event.created_at() {
+                return Err(EventStoreError::StorageError(format!(
+                    "the stored date of event {} does not match the event",
+                    envelope.id
+                )));
+            }
+            f(envelope)?;
+            any = true;
+        }
+        Ok(any)
+    }
+
+    /// Fail with AggregateNotFound when nothing is stored for the aggregate
+    fn ensure_exists(&self, aggregate_id: &str) -> Result<(), EventStoreError> {
+        let exists: bool = self.db.query_row(
+            "SELECT EXISTS (SELECT 1 FROM events WHERE aggregate_id = ?)",
+            [aggregate_id],
+            |row| row.get(0),
+        )?;
+        if exists {
+            Ok(())
+        } else {
+            Err(EventStoreError::AggregateNotFound(aggregate_id.to_string()))
+        }
+    }
+
+    /// Chain the events stored before the hash chain. Returns the ids of the rows.
+    fn chain_unhashed_events(&self) -> Result<HashSet<i64>, EventStoreError> {
+        let mut chained = HashSet::new();
+        for aggregate_id in self.aggregate_ids()? {
+            let mut envelopes = self.get_events(&aggregate_id)?;
+            envelopes.sort_by_key(|envelope| envelope.sequence);
+
+            let mut previous_hash: Option<String> = None;
+            for envelope in envelopes {
+                let hash = match envelope.hash {
+                    Some(hash) => hash,
+                    None => {
+                        let hash = chain_hash(&envelope, previous_hash.as_deref());
+                        let id: i64 = self.db.query_row(
+                            "UPDATE events SET hash = ? WHERE uuid = ? RETURNING id",
+                            params![&hash, envelope.id.to_string()],
+                            |row| row.get(0),
+                        )?;
+                        chained.insert(id);
+                        hash
+                    }
+                };
+                previous_hash = Some(hash);
+            }
+        }
+        Ok(chained)
+    }
+}
+
+impl EventStore for SqliteEventStore {
+    fn init(&self) -> Result<(), EventStoreError> {
+        // created_at is the date the event happened, copied from the event to sort on.
+        // recorded_at is the date it was stored.
         self.db
             .execute(
                 "CREATE TABLE IF NOT EXISTS events (
                     id INTEGER PRIMARY KEY,
-                    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                    uuid TEXT NOT NULL UNIQUE,
                     aggregate_id TEXT NOT NULL,
-                    event TEXT NOT NULL
+                    sequence INTEGER NOT NULL,
+                    event_type TEXT NOT NULL,
+                    schema_version INTEGER NOT NULL,
+                    created_at DATETIME NOT NULL,
+                    recorded_at DATETIME NOT NULL,
+                    source TEXT,
+                    comment TEXT,
+                    event TEXT NOT NULL,
+                    hash TEXT,
+                    UNIQUE (aggregate_id, sequence)
                 )",
                 params![],
             )
             .map_err(|_| EventStoreError::Unknown)?;
+        // A snapshot holds the folded state of a projection, up to and including an event
+        self.db
+            .execute(
+                "CREATE TABLE IF NOT EXISTS snapshots (
+                    aggregate_id TEXT NOT NULL,
+                    projection TEXT NOT NULL,
+                    version TEXT NOT NULL,
+                    sequence INTEGER NOT NULL,
+                    created_at DATETIME NOT NULL,
+                    state TEXT NOT NULL,
+                    PRIMARY KEY (aggregate_id, projection)
+                )",
+                params![],
+            )
+            .map_err(|_| EventStoreError::Unknown)?;
+        self.outdated_layout.set(self.has_outdated_layout()?);
         Ok(())
     }
-}
 
-impl EventStore for SqliteEventStore {
-    fn get_events(&self, aggregate_id: &str) -> Result<Vec<AccountEvent>, EventStoreError> {
-        let mut stmt = self
-            .db
-            .prepare("SELECT event FROM events WHERE aggregate_id = ? ORDER BY created_at ASC")?;
-        let events = stmt
-            .query_map([&aggregate_id], |row| {
-                let event: String = row.get(0)?;
-                Ok(event)
-            })
-            .map_err(|_| EventStoreError::Unknown)?
-            .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+    /// Bring a database written by an older version up to date. Moves the events of
+    /// the table from before the envelope into the current layout and chains the
+    /// events that have no hash. Returns the number of events rewritten.
+    fn migrate(&self) -> Result<usize, EventStoreError> {
+        let tx = self.db.unchecked_transaction()?;
+        if self.has_legacy_layout()? {
+            self.move_legacy_events()?;
+        }
+        self.init()?;
+        if !self.columns()?.iter().any(|column| column == "hash") {
+            self.db
+                .execute("ALTER TABLE events ADD COLUMN hash TEXT", [])?;
+        }
+        self.outdated_layout.set(self.has_outdated_layout()?);
+        let chained = self.chain_unhashed_events()?;
+        tx.commit()?;
+        Ok(chained.len())
+    }
+
+    fn get_events(&self, aggregate_id: &str) -> Result<Vec<EventEnvelope>, EventStoreError> {
+        let events = self.query_events(aggregate_id, 0, &EventQuery::new())?;
 
         if events.is_empty() {
             Err(EventStoreError::AggregateNotFound(aggregate_id.to_string()))
         } else {
-            Ok(events
-                .iter()
-                .map(|event| serde_json::from_str(event).unwrap())
-                .collect())
+            Ok(events)
         }
     }
 
-    fn persist(&self, aggregate_id: &str, events: &[AccountEvent]) -> Result<(), EventStoreError> {
-        // TODO: we now store the created_at in both the serialized event and in the database.
-        let mut stmt = self
-            .db
-            .prepare("INSERT INTO events (aggregate_id, created_at, event) VALUES (?, ?, ?)")?;
-        for event in events {
-            let dt = event.created_at();
-            let event = serde_json::to_string(event)?;
+    fn get_events_after(
+        &self,
+        aggregate_id: &str,
+        sequence: u64,
+    ) -> Result<Vec<EventEnvelope>, EventStoreError> {
+        self.query_events(aggregate_id, sequence, &EventQuery::new())
+    }
 
-            stmt.execute(params![&aggregate_id, &dt, &event])
-                .expect("Failed to insert");
+    fn query(
+        &self,
+        aggregate_id: &str,
+        query: &EventQuery,
+    ) -> Result<Vec<EventEnvelope>, EventStoreError> {
+        let events = self.query_events(aggregate_id, 0, query)?;
+        if events.is_empty() {
+            self.ensure_exists(aggregate_id)?;
+        }
+        Ok(events)
+    }
+
+    fn stream_events(
+        &self,
+        aggregate_id: &str,
+        query: &EventQuery,
+        f: &mut dyn FnMut(EventEnvelope) -> Result<(), EventStoreError>,
+    ) -> Result<(), EventStoreError> {
+        if !self.each_event(aggregate_id, 0, query, f)? {
+            self.ensure_exists(aggregate_id)?;
         }
         Ok(())
     }
+
+    fn persist(&self, aggregate_id: &str, events: &[EventEnvelope]) -> Result<(), EventStoreError> {
+        self.insert(aggregate_id, events, None)
+    }
+
+    fn persist_expected(
+        &self,
+        aggregate_id: &str,
+        events: &[EventEnvelope],
+        expected_version: u64,
+    ) -> Result<(), EventStoreError> {
+        self.insert(aggregate_id, events, Some(expected_version))
+    }
+
+    fn aggregate_ids(&self) -> Result<Vec<String>, EventStoreError> {
+        let aggregate_ids = self
+            .db
+            .prepare("SELECT DISTINCT aggregate_id FROM events ORDER BY aggregate_id ASC")?
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+        Ok(aggregate_ids)
+    }
+
+    fn get_snapshot(
+        &self,
+        aggregate_id: &str,
+        projection: &str,
+    ) -> Result<Option<Snapshot>, EventStoreError> {
+        let snapshot = self
+            .db
+            .query_row(
+                "SELECT projection, version, sequence, created_at, state FROM snapshots
+                WHERE aggregate_id = ? AND projection = ?",
+                [aggregate_id, projection],
+                |row| {
+                    Ok(Snapshot {
+                        projection: row.get(0)?,
+                        version: row.get(1)?,
+                        sequence: row.get(2)?,
+                        created_at: row.get(3)?,
+                        state: row.get(4)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(snapshot)
+    }
+
+    fn save_snapshot(
+        &self,
+        aggregate_id: &str,
+        snapshot: &Snapshot,
+    ) -> Result<(), EventStoreError> {
+        self.db.execute(
+            "INSERT OR REPLACE INTO snapshots
+            (aggregate_id, projection, version, sequence, created_at, state)
+            VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                &aggregate_id,
+                snapshot.projection,
+                snapshot.version,
+                snapshot.sequence,
+                snapshot.created_at,
+                snapshot.state
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+/// The envelope in a row selected by `each_event`
+fn envelope_from_row(row: &Row) -> Result<EventEnvelope, rusqlite::Error> {
+    let id: String = row.get(0)?;
+    let event: String = row.get(7)?;
+    Ok(EventEnvelope {
+        id: Uuid::parse_str(&id).map_err(|err| conversion_failure(0, err))?,
+        sequence: row.get(1)?,
+        event_type: row.get(2)?,
+        schema_version: row.get(3)?,
+        recorded_at: row.get(4)?,
+        source: row.get(5)?,
+        comment: row.get(6)?,
+        hash: row.get(8)?,
+        event: serde_json::from_str(&event).map_err(|err| conversion_failure(7, err))?,
+    })
+}
+
+/// The conditions of the query, combined in SQL, and the values to bind to them
+fn query_filters(query: &EventQuery) -> (String, Vec<Box<dyn ToSql>>) {
+    let mut conditions = vec!["1".to_string()];
+    let mut values: Vec<Box<dyn ToSql>> = vec![];
+    if let Some(from) = query.from {
+        conditions.push("date(created_at) >= ?".to_string());
+        values.push(Box::new(from));
+    }
+    if let Some(until) = query.until {
+        conditions.push("date(created_at) <= ?".to_string());
+        values.push(Box::new(until));
+    }
+    if !query.event_types.is_empty() {
+        let placeholders = vec!["?"; query.event_types.len()].join(", ");
+        conditions.push(format!("event_type IN ({})", placeholders));
+        for event_type in &query.event_types {
+            values.push(Box::new(event_type.clone()));
+        }
+    }
+    if let Some(identifier) = &query.identifier {
+        // Events are stored as {"StocksBought": {"identifier": {"ticker": ...}, ...}}
+        conditions.push(
+            "json_extract(event, '$.' || event_type || '.identifier.ticker') = ?".to_string(),
+        );
+        values.push(Box::new(identifier.ticker.clone()));
+    }
+    (conditions.join(" AND "), values)
+}
+
+/// A column that could be read, but not parsed
+fn conversion_failure(
+    column: usize,
+    err: impl std::error::Error + Send + Sync + 'static,
+) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(column, rusqlite::types::Type::Text, Box::new(err))
 }
 
 impl From<rusqlite::Error> for EventStoreError {
@@ -85,18 +504,24 @@ impl From<serde_json::Error> for EventStoreError {
 mod tests {
     use tempfile::TempDir;
 
-    use crate::{date_utils::fixtures::iphone_launched_at, events::StocksBought};
+    use crate::{
+        date_utils::fixtures::iphone_launched_at,
+        envelope::SCHEMA_VERSION,
+        events::{AccountEvent, StocksBought},
+        hash_chain::verify_chain,
+        value_objects::StockIdentifier,
+    };
 
     use super::*;
     #[test]
     fn test_sqlite_persist() {
         let (db_file, event_store) = setup_db();
-        let events = vec![AccountEvent::new_stocks_bought(
+        let events = vec![EventEnvelope::new(AccountEvent::new_stocks_bought(
             iphone_launched_at(),
             10.0,
             "100.00 USD".to_string(),
             "AAPL".to_string(),
-        )];
+        ))];
         event_store.persist("123", &events).unwrap();
         let events = event_store.get_events("123").unwrap();
 
@@ -108,12 +533,12 @@ mod tests {
     #[test]
     fn test_sqlite_get_events() {
         let (db_file, event_store) = setup_db();
-        let events = vec![AccountEvent::new_stocks_bought(
+        let events = vec![EventEnvelope::new(AccountEvent::new_stocks_bought(
             iphone_launched_at(),
             10.0,
             "100.00 USD".to_string(),
             "AAPL".to_string(),
-        )];
+        ))];
         event_store.persist("123", &events).unwrap();
         let events = event_store.get_events("123").unwrap();
 
@@ -128,18 +553,18 @@ mod tests {
         // But post-date the AAPL event by 1 second, so it should be first
         let (db_file, event_store) = setup_db();
         let events = vec![
-            AccountEvent::new_stocks_bought(
+            EventEnvelope::new(AccountEvent::new_stocks_bought(
                 iphone_launched_at() + chrono::Duration::seconds(1),
                 10.0,
                 "100.00 USD".to_string(),
                 "MSFT".to_string(),
-            ),
-            AccountEvent::new_stocks_bought(
+            )),
+            EventEnvelope::new(AccountEvent::new_stocks_bought(
                 iphone_launched_at(),
                 10.0,
                 "100.00 USD".to_string(),
                 "AAPL".to_string(),
-            ),
+            )),
         ];
         event_store.persist("123", &events).unwrap();
         let events = event_store.get_events("123").unwrap();
@@ -147,7 +572,7 @@ mod tests {
         assert_eq!(events.len(), 2);
         let tickers = events
             .iter()
-            .map(|e| match e {
+            .map(|e| match &e.event {
                 AccountEvent::StocksBought(StocksBought { identifier, .. }) => {
                     identifier.ticker.clone()
                 }
@@ -160,6 +585,35 @@ mod tests {
         db_file.close().unwrap();
     }
 
+    #[test]
+    fn test_sqlite_get_events_returns_envelopes() {
+        let (db_file, event_store) = setup_db();
+        let envelope = EventEnvelope::new(AccountEvent::new_cash_deposited(
+            iphone_launched_at(),
+            "100.00 USD".to_string(),
+        ))
+        .with_source("add".to_string())
+        .with_comment(Some("Birthday money".to_string()));
+        event_store
+            .persist("123", std::slice::from_ref(&envelope))
+            .unwrap();
+        event_store
+            .persist("123", &[EventEnvelope::new(envelope.event.clone())])
+            .unwrap();
+
+        let events = event_store.get_events("123").unwrap();
+
+        assert_eq!(events[0].id, envelope.id);
+        assert_eq!(events[0].sequence, 1);
+        assert_eq!(events[0].event_type, "CashDeposited");
+        assert_eq!(events[0].recorded_at, envelope.recorded_at);
+        assert_eq!(events[0].source, Some("add".to_string()));
+        assert_eq!(events[0].comment, Some("Birthday money".to_string()));
+        assert_eq!(events[1].sequence, 2);
+
+        db_file.close().unwrap();
+    }
+
     #[test]
     fn test_sqlite_get_events_not_found() {
         let (db_file, event_store) = setup_db();
@@ -170,6 +624,376 @@ mod tests {
         db_file.close().unwrap();
     }
 
+    #[test]
+    fn test_sqlite_aggregate_ids() {
+        let (db_file, event_store) = setup_db();
+        let event = AccountEvent::new_cash_deposited(iphone_launched_at(), "1.00 USD".to_string());
+        for aggregate_id in ["personal", "pension", "personal"] {
+            event_store
+                .persist(aggregate_id, &[EventEnvelope::new(event.clone())])
+                .unwrap();
+        }
+
+        assert_eq!(
+            event_store.aggregate_ids().unwrap(),
+            vec!["pension", "personal"]
+        );
+
+        db_file.close().unwrap();
+    }
+
+    #[test]
+    fn test_sqlite_get_events_legacy_layout_asks_to_migrate() {
+        let (db_file, event_store) = setup_fixture_db(include_str!("fixtures/v0.sql"));
+        let events = event_store.get_events("ber");
+
+        assert!(events
+            .unwrap_err()
+            .to_string()
+            .contains("bullboard migrate"));
+
+        db_file.close().unwrap();
+    }
+
+    #[test]
+    fn test_sqlite_migrate_legacy_layout() {
+        let (db_file, event_store) = setup_fixture_db(include_str!("fixtures/v0.sql"));
+
+        assert_eq!(event_store.migrate().unwrap(), 4);
+
+        let events = event_store.get_events("ber").unwrap();
+        let event_types = events
+            .iter()
+            .map(|e| e.event_type.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            event_types,
+            vec!["CashDeposited", "StocksBought", "DividendPaid"]
+        );
+        assert_eq!(
+            events.iter().map(|e| e.sequence).collect::<Vec<u64>>(),
+            vec![1, 2, 3]
+        );
+        assert!(
+            events
+                .iter()
+                .all(|e| e.schema_version == SCHEMA_VERSION
+                    && e.source == Some("migrate".to_string()))
+        );
+        assert_eq!(event_store.get_events("other").unwrap()[0].sequence, 1);
+
+        db_file.close().unwrap();
+    }
+
+    #[test]
+    fn test_sqlite_migrate_chains_events() {
+        let (db_file, event_store) = setup_fixture_db(include_str!("fixtures/v0.sql"));
+
+        assert_eq!(event_store.migrate().unwrap(), 4);
+
+        let head = verify_chain(event_store.get_events("ber").unwrap()).unwrap();
+        assert_eq!(head.events, 3);
+
+        db_file.close().unwrap();
+    }
+
+    #[test]
+    fn test_sqlite_persist_chains_events() {
+        let (db_file, event_store) = setup_db();
+        let event = AccountEvent::new_cash_deposited(iphone_launched_at(), "1.00 USD".to_string());
+        event_store
+            .persist("123", &[EventEnvelope::new(event.clone())])
+            .unwrap();
+        event_store
+            .persist(
+                "123",
+                &[EventEnvelope::new(event.clone()), EventEnvelope::new(event)],
+            )
+            .unwrap();
+
+        let head = verify_chain(event_store.get_events("123").unwrap()).unwrap();
+        assert_eq!(head.events, 3);
+
+        db_file.close().unwrap();
+    }
+
+    #[test]
+    fn test_sqlite_verify_detects_edited_event() {
+        let (db_file, event_store) = setup_db();
+        let events = vec![
+            EventEnvelope::new(AccountEvent::new_cash_deposited(
+                iphone_launched_at(),
+                "1.00 USD".to_string(),
+            )),
+            EventEnvelope::new(AccountEvent::new_cash_deposited(
+                iphone_launched_at(),
+                "2.00 USD".to_string(),
+            )),
+        ];
+        event_store.persist("123", &events).unwrap();
+        event_store
+            .db
+            .execute(
+                "UPDATE events SET event = replace(event, '1.00', '1000.00') WHERE sequence = 1",
+                [],
+            )
+            .unwrap();
+
+        let broken = verify_chain(event_store.get_events("123").unwrap()).unwrap_err();
+        assert_eq!(broken.sequence, 1);
+        assert_eq!(broken.id, events[0].id);
+
+        db_file.close().unwrap();
+    }
+
+    #[test]
+    fn test_sqlite_detects_edited_created_at() {
+        let (db_file, event_store) = setup_db();
+        let events = vec![
+            EventEnvelope::new(AccountEvent::new_cash_deposited(
+                iphone_launched_at(),
+                "1.00 USD".to_string(),
+            )),
+            EventEnvelope::new(AccountEvent::new_cash_deposited(
+                iphone_launched_at(),
+                "2.00 USD".to_string(),
+            )),
+        ];
+        event_store.persist("123", &events).unwrap();
+        // Moving an event without changing it would reorder the events, unseen by the chain
+        event_store
+            .db
+            .execute(
+                "UPDATE events SET created_at = '1999-01-01T00:00:00' WHERE sequence = 2",
+                [],
+            )
+            .unwrap();
+
+        let err = event_store.get_events("123").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains(&format!("the stored date of event {}", events[1].id)));
+
+        db_file.close().unwrap();
+    }
+
+    #[test]
+    fn test_sqlite_save_snapshot_replaces_the_previous() {
+        let (db_file, event_store) = setup_db();
+        let snapshot = Snapshot {
+            projection: "dashboard fifo".to_string(),
+            version: "1".to_string(),
+            sequence: 1,
+            created_at: iphone_launched_at(),
+            state: "{}".to_string(),
+        };
+        let later = Snapshot {
+            sequence: 2,
+            ..snapshot.clone()
+        };
+
+        assert_eq!(
+            event_store.get_snapshot("123", "dashboard fifo").unwrap(),
+            None
+        );
+        event_store.save_snapshot("123", &snapshot).unwrap();
+        event_store.save_snapshot("123", &later).unwrap();
+
+        assert_eq!(
+            event_store.get_snapshot("123", "dashboard fifo").unwrap(),
+            Some(later)
+        );
+        assert_eq!(
+            event_store.get_snapshot("123", "dashboard lifo").unwrap(),
+            None
+        );
+
+        db_file.close().unwrap();
+    }
+
+    #[test]
+    fn test_sqlite_persist_expected() {
+        let (db_file, event_store) = setup_db();
+        let event = AccountEvent::new_cash_deposited(iphone_launched_at(), "1.00 USD".to_string());
+        event_store
+            .persist_expected("123", &[EventEnvelope::new(event.clone())], 0)
+            .unwrap();
+        event_store
+            .persist("123", &[EventEnvelope::new(event.clone())])
+            .unwrap();
+
+        let result = event_store.persist_expected("123", &[EventEnvelope::new(event)], 1);
+
+        match result {
+            Err(EventStoreError::ConcurrencyConflict {
+                expected, actual, ..
+            }) => assert_eq!((expected, actual), (1, 2)),
+            _ => panic!("Expected a concurrency conflict"),
+        }
+        assert_eq!(event_store.get_events("123").unwrap().len(), 2);
+
+        db_file.close().unwrap();
+    }
+
+    #[test]
+    fn test_sqlite_persist_all_or_nothing() {
+        let (db_file, event_store) = setup_db();
+        let envelope = EventEnvelope::new(AccountEvent::new_cash_deposited(
+            iphone_launched_at(),
+            "1.00 USD".to_string(),
+        ));
+
+        // The second event has the id of the first, which cannot be stored twice
+        let result = event_store.persist("123", &[envelope.clone(), envelope]);
+
+        assert!(matches!(result, Err(EventStoreError::StorageError(_))));
+        assert!(matches!(
+            event_store.get_events("123"),
+            Err(EventStoreError::AggregateNotFound(_))
+        ));
+
+        db_file.close().unwrap();
+    }
+
+    #[test]
+    fn test_sqlite_query_filters_like_the_query() {
+        let (db_file, event_store) = setup_db();
+        let day = |days| iphone_launched_at() + chrono::Duration::days(days);
+        let msft = EventEnvelope::new(AccountEvent::new_stocks_bought(
+            day(1),
+            1.0,
+            "10.00 USD".to_string(),
+            "MSFT".to_string(),
+        ));
+        let events = vec![
+            EventEnvelope::new(AccountEvent::new_stocks_bought(
+                day(0),
+                1.0,
+                "10.00 USD".to_string(),
+                "AAPL".to_string(),
+            )),
+            msft.clone(),
+            EventEnvelope::new(AccountEvent::new_cash_deposited(
+                day(2),
+                "1.00 USD".to_string(),
+            )),
+            EventEnvelope::new(AccountEvent::new_dividend_paid(
+                day(3),
+                "0.10 USD".to_string(),
+                "AAPL".to_string(),
+            )),
+            EventEnvelope::new(AccountEvent::new_event_voided(day(4), msft.id, msft.event)),
+        ];
+        event_store.persist("123", &events).unwrap();
+
+        let queries = [
+            EventQuery::new(),
+            EventQuery::new().from(day(1).date()).until(day(2).date()),
+            EventQuery::new().with_event_types(&["StocksBought", "DividendPaid"]),
+            EventQuery::new().with_identifier(StockIdentifier::from("AAPL")),
+            EventQuery::new()
+                .until(day(0).date())
+                .with_identifier(StockIdentifier::from("AAPL")),
+        ];
+        for query in queries {
+            let ids = |envelopes: Vec<EventEnvelope>| {
+                envelopes
+                    .into_iter()
+                    .map(|envelope| envelope.id)
+                    .collect::<Vec<Uuid>>()
+            };
+            let expected = event_store
+                .get_events("123")
+                .unwrap()
+                .into_iter()
+                .filter(|envelope| query.matches(envelope))
+                .collect();
+
+            assert_eq!(
+                ids(event_store.query("123", &query).unwrap()),
+                ids(expected),
+                "{:?}",
+                query
+            );
+        }
+        let aapl = EventQuery::new().with_identifier(StockIdentifier::from("AAPL"));
+        let event_types = event_store
+            .query("123", &aapl)
+            .unwrap()
+            .into_iter()
+            .map(|envelope| envelope.event_type)
+            .collect::<Vec<String>>();
+        assert_eq!(
+            event_types,
+            vec!["StocksBought", "DividendPaid", "EventVoided"]
+        );
+        assert!(matches!(
+            event_store.query("456", &EventQuery::new()),
+            Err(EventStoreError::AggregateNotFound(_))
+        ));
+
+        db_file.close().unwrap();
+    }
+
+    #[test]
+    fn test_sqlite_stream_events() {
+        let (db_file, event_store) = setup_db();
+        let day = |days| iphone_launched_at() + chrono::Duration::days(days);
+        let events = [2, 0, 1].map(|days| {
+            EventEnvelope::new(AccountEvent::new_cash_deposited(
+                day(days),
+                "1.00 USD".to_string(),
+            ))
+        });
+        event_store.persist("123", &events).unwrap();
+
+        let mut sequences = vec![];
+        event_store
+            .stream_events("123", &EventQuery::new(), &mut |envelope| {
+                sequences.push(envelope.sequence);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(sequences, vec![2, 3, 1]);
+
+        let mut streamed = 0;
+        let result = event_store.stream_events("123", &EventQuery::new(), &mut |_| {
+            streamed += 1;
+            Err(EventStoreError::Unknown)
+        });
+        assert!(matches!(result, Err(EventStoreError::Unknown)));
+        assert_eq!(streamed, 1);
+
+        let result = event_store.stream_events("456", &EventQuery::new(), &mut |_| Ok(()));
+        assert!(matches!(result, Err(EventStoreError::AggregateNotFound(_))));
+
+        db_file.close().unwrap();
+    }
+
+    #[test]
+    fn test_sqlite_migrate_current_database() {
+        let (db_file, event_store) = setup_db();
+
+        assert_eq!(event_store.migrate().unwrap(), 0);
+
+        db_file.close().unwrap();
+    }
+
+    /// A database in the state an older version left it
+    fn setup_fixture_db(sql: &str) -> (TempDir, SqliteEventStore) {
+        let temp_dir = tempfile::tempdir().expect("Failed to create tmp directory");
+        let db_path = temp_dir.path().join("test.db");
+        let db_path_str = db_path.to_str().expect("Failed to convert path to string");
+
+        Connection::open(db_path_str)
+            .expect("Failed to open database")
+            .execute_batch(sql)
+            .expect("Failed to load fixture");
+        let event_store = SqliteEventStore::new(db_path_str).expect("Failed to create event store");
+
+        (temp_dir, event_store)
+    }
+
     fn setup_db() -> (TempDir, SqliteEventStore) {
         let temp_dir = tempfile::tempdir().expect("Failed to create tmp directory");
         let db_path = temp_dir.path().join("test.db");
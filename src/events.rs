@@ -1,6 +1,9 @@
-use crate::value_objects::{Amount, Currency, StockIdentifier};
+use crate::value_objects::{Amount, Currency, SplitRatio, StockIdentifier};
 use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt::Display};
+use uuid::Uuid;
 
 /// A stock was bought
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,6 +16,9 @@ pub struct StocksBought {
     pub price: Amount,
     /// The ticker of the stock
     pub identifier: StockIdentifier,
+    /// The fee or commission paid to the broker for the whole trade
+    #[serde(default)]
+    pub fee: Option<Amount>,
 }
 
 impl StocksBought {
@@ -24,12 +30,72 @@ impl StocksBought {
             amount,
             price,
             identifier,
+            fee: None,
         }
     }
 
     pub(crate) fn currency(&self) -> Currency {
         self.price.currency.clone()
     }
+
+    pub fn fee(&self) -> Amount {
+        self.fee.clone().unwrap_or(Amount::zero(self.currency()))
+    }
+
+    /// The total paid, including the fee
+    pub fn total(&self) -> Amount {
+        self.price.clone() * self.amount + self.fee()
+    }
+
+    /// The price paid for each stock, with the fee spread over all stocks
+    pub fn price_including_fee(&self) -> Amount {
+        if self.amount <= 0.0 {
+            return self.price.clone();
+        }
+        let amount = Decimal::try_from(self.amount).unwrap();
+        Amount::new(self.total().num / amount, self.currency())
+    }
+}
+
+/// A stock was sold
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StocksSold {
+    /// Event creation time
+    pub created_at: NaiveDateTime,
+    /// The amount of stocks of this type sold. Fractional, because some assets allow fractions
+    pub amount: f64,
+    /// The price received for each stock
+    pub price: Amount,
+    /// The ticker of the stock
+    pub identifier: StockIdentifier,
+    /// The fee or commission paid to the broker for the whole trade
+    #[serde(default)]
+    pub fee: Option<Amount>,
+}
+
+impl StocksSold {
+    pub fn new(created_at: NaiveDateTime, amount: f64, price: String, ticker: String) -> Self {
+        let price = Amount::from(price);
+        let identifier = StockIdentifier::from(ticker);
+        Self {
+            created_at,
+            amount,
+            price,
+            identifier,
+            fee: None,
+        }
+    }
+
+    pub fn fee(&self) -> Amount {
+        self.fee
+            .clone()
+            .unwrap_or(Amount::zero(self.price.currency.clone()))
+    }
+
+    /// The total received, after the fee
+    pub fn total(&self) -> Amount {
+        self.price.clone() * self.amount - self.fee()
+    }
 }
 
 /// A price was obtained for a stock
@@ -53,10 +119,6 @@ impl PriceObtained {
             identifier,
         }
     }
-
-    pub(crate) fn currency(&self) -> Currency {
-        self.price.currency.clone()
-    }
 }
 
 /// A dividend was paid for a stock
@@ -64,10 +126,16 @@ impl PriceObtained {
 pub struct DividendPaid {
     /// The time the dividend was paid
     pub created_at: NaiveDateTime,
-    /// The amount of dividend paid per stock on hand
+    /// The amount of dividend paid per stock on hand, before tax
     pub price: Amount,
     /// The ticker of the stock
     pub identifier: StockIdentifier,
+    /// The tax withheld at the source per stock on hand
+    #[serde(default)]
+    pub withheld_tax: Option<Amount>,
+    /// The country that withheld the tax, e.g. US
+    #[serde(default)]
+    pub country: Option<String>,
 }
 
 impl DividendPaid {
@@ -78,6 +146,165 @@ impl DividendPaid {
             created_at,
             price,
             identifier,
+            withheld_tax: None,
+            country: None,
+        }
+    }
+
+    pub fn withheld_tax(&self) -> Amount {
+        self.withheld_tax
+            .clone()
+            .unwrap_or(Amount::zero(self.price.currency.clone()))
+    }
+}
+
+/// A stock was split, or reverse split
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockSplit {
+    /// The time the split took effect
+    pub created_at: NaiveDateTime,
+    /// The number of new stocks for the number of old stocks
+    pub ratio: SplitRatio,
+    /// The ticker of the stock
+    pub identifier: StockIdentifier,
+}
+
+impl StockSplit {
+    pub fn new(created_at: NaiveDateTime, ratio: String, identifier: String) -> Self {
+        let ratio = SplitRatio::from(ratio);
+        let identifier = StockIdentifier::from(identifier);
+        Self {
+            created_at,
+            ratio,
+            identifier,
+        }
+    }
+}
+
+/// A fee was charged, not for a trade but for e.g. custody or the account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeCharged {
+    /// The time the fee was charged
+    pub created_at: NaiveDateTime,
+    /// The fee charged
+    pub amount: Amount,
+}
+
+impl FeeCharged {
+    pub fn new(created_at: NaiveDateTime, amount: String) -> Self {
+        let amount = Amount::from(amount);
+        Self { created_at, amount }
+    }
+}
+
+/// Cash was deposited into the account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CashDeposited {
+    /// The time the cash was deposited
+    pub created_at: NaiveDateTime,
+    /// The sum deposited
+    pub amount: Amount,
+}
+
+impl CashDeposited {
+    pub fn new(created_at: NaiveDateTime, amount: String) -> Self {
+        let amount = Amount::from(amount);
+        Self { created_at, amount }
+    }
+}
+
+/// Cash was withdrawn from the account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CashWithdrawn {
+    /// The time the cash was withdrawn
+    pub created_at: NaiveDateTime,
+    /// The sum withdrawn
+    pub amount: Amount,
+}
+
+impl CashWithdrawn {
+    pub fn new(created_at: NaiveDateTime, amount: String) -> Self {
+        let amount = Amount::from(amount);
+        Self { created_at, amount }
+    }
+}
+
+/// An exchange rate was obtained for a currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExchangeRateObtained {
+    /// The time the rate was obtained
+    pub created_at: NaiveDateTime,
+    /// The currency that was priced
+    pub currency: Currency,
+    /// The price of one unit of the currency, in another currency
+    pub price: Amount,
+}
+
+impl ExchangeRateObtained {
+    pub fn new(created_at: NaiveDateTime, price: String, currency: String) -> Self {
+        let price = Amount::from(price);
+        let currency = Currency::from(currency);
+        Self {
+            created_at,
+            currency,
+            price,
+        }
+    }
+}
+
+/// An account was opened, before anything happened in it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountOpened {
+    /// Event creation time
+    pub created_at: NaiveDateTime,
+}
+
+impl AccountOpened {
+    pub fn new(created_at: NaiveDateTime) -> Self {
+        Self { created_at }
+    }
+}
+
+/// An earlier event was recorded by mistake, and should be ignored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventVoided {
+    /// Event creation time
+    pub created_at: NaiveDateTime,
+    /// The id of the envelope of the voided event
+    pub event_id: Uuid,
+    /// The voided event, as it was, to show in the journal
+    pub event: Box<AccountEvent>,
+}
+
+impl EventVoided {
+    pub fn new(created_at: NaiveDateTime, event_id: Uuid, event: AccountEvent) -> Self {
+        let event = Box::new(event);
+        Self {
+            created_at,
+            event_id,
+            event,
+        }
+    }
+}
+
+/// An earlier event was recorded with the wrong values, and should be replaced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventAmended {
+    /// Event creation time
+    pub created_at: NaiveDateTime,
+    /// The id of the envelope of the amended event
+    pub event_id: Uuid,
+    /// The event with the corrected values
+    pub event: Box<AccountEvent>,
+}
+
+impl EventAmended {
+    pub fn new(created_at: NaiveDateTime, event_id: Uuid, event: AccountEvent) -> Self {
+        let event = Box::new(event);
+        Self {
+            created_at,
+            event_id,
+            event,
         }
     }
 }
@@ -85,8 +312,17 @@ impl DividendPaid {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum AccountEvent {
     StocksBought(StocksBought),
+    StocksSold(StocksSold),
     PriceObtained(PriceObtained),
     DividendPaid(DividendPaid),
+    StockSplit(StockSplit),
+    CashDeposited(CashDeposited),
+    CashWithdrawn(CashWithdrawn),
+    FeeCharged(FeeCharged),
+    ExchangeRateObtained(ExchangeRateObtained),
+    AccountOpened(AccountOpened),
+    EventVoided(EventVoided),
+    EventAmended(EventAmended),
 }
 
 impl AccountEvent {
@@ -100,6 +336,16 @@ impl AccountEvent {
         AccountEvent::StocksBought(stocks_bought)
     }
 
+    pub fn new_stocks_sold(
+        created_at: NaiveDateTime,
+        amount: f64,
+        price: String,
+        ticker: String,
+    ) -> Self {
+        let stocks_sold = StocksSold::new(created_at, amount, price, ticker);
+        AccountEvent::StocksSold(stocks_sold)
+    }
+
     pub fn new_price_obtained(
         created_at: NaiveDateTime,
         price: String,
@@ -114,11 +360,373 @@ impl AccountEvent {
         AccountEvent::DividendPaid(dividend_paid)
     }
 
-    pub(crate) fn created_at(&self) -> NaiveDateTime {
+    pub fn new_stock_split(created_at: NaiveDateTime, ratio: String, identifier: String) -> Self {
+        let stock_split = StockSplit::new(created_at, ratio, identifier);
+        AccountEvent::StockSplit(stock_split)
+    }
+
+    pub fn new_cash_deposited(created_at: NaiveDateTime, amount: String) -> Self {
+        let cash_deposited = CashDeposited::new(created_at, amount);
+        AccountEvent::CashDeposited(cash_deposited)
+    }
+
+    pub fn new_cash_withdrawn(created_at: NaiveDateTime, amount: String) -> Self {
+        let cash_withdrawn = CashWithdrawn::new(created_at, amount);
+        AccountEvent::CashWithdrawn(cash_withdrawn)
+    }
+
+    pub fn new_fee_charged(created_at: NaiveDateTime, amount: String) -> Self {
+        let fee_charged = FeeCharged::new(created_at, amount);
+        AccountEvent::FeeCharged(fee_charged)
+    }
+
+    pub fn new_exchange_rate_obtained(
+        created_at: NaiveDateTime,
+        price: String,
+        currency: String,
+    ) -> Self {
+        let exchange_rate_obtained = ExchangeRateObtained::new(created_at, price, currency);
+        AccountEvent::ExchangeRateObtained(exchange_rate_obtained)
+    }
+
+    pub fn new_account_opened(created_at: NaiveDateTime) -> Self {
+        AccountEvent::AccountOpened(AccountOpened::new(created_at))
+    }
+
+    pub fn new_event_voided(
+        created_at: NaiveDateTime,
+        event_id: Uuid,
+        event: AccountEvent,
+    ) -> Self {
+        let event_voided = EventVoided::new(created_at, event_id, event);
+        AccountEvent::EventVoided(event_voided)
+    }
+
+    pub fn new_event_amended(
+        created_at: NaiveDateTime,
+        event_id: Uuid,
+        event: AccountEvent,
+    ) -> Self {
+        let event_amended = EventAmended::new(created_at, event_id, event);
+        AccountEvent::EventAmended(event_amended)
+    }
+
+    /// Replace the price, or the amount of cash deposited, withdrawn or charged
+    pub fn with_price(self, price: String) -> Result<Self, UnsupportedField> {
+        let price = Amount::from(price);
+        let event = match self {
+            AccountEvent::StocksBought(event) => {
+                AccountEvent::StocksBought(StocksBought { price, ..event })
+            }
+            AccountEvent::StocksSold(event) => {
+                AccountEvent::StocksSold(StocksSold { price, ..event })
+            }
+            AccountEvent::PriceObtained(event) => {
+                AccountEvent::PriceObtained(PriceObtained { price, ..event })
+            }
+            AccountEvent::DividendPaid(event) => {
+                AccountEvent::DividendPaid(DividendPaid { price, ..event })
+            }
+            AccountEvent::ExchangeRateObtained(event) => {
+                AccountEvent::ExchangeRateObtained(ExchangeRateObtained { price, ..event })
+            }
+            AccountEvent::CashDeposited(event) => AccountEvent::CashDeposited(CashDeposited {
+                amount: price,
+                ..event
+            }),
+            AccountEvent::CashWithdrawn(event) => AccountEvent::CashWithdrawn(CashWithdrawn {
+                amount: price,
+                ..event
+            }),
+            AccountEvent::FeeCharged(event) => AccountEvent::FeeCharged(FeeCharged {
+                amount: price,
+                ..event
+            }),
+            event => return Err(UnsupportedField::new(&event, "price")),
+        };
+        Ok(event)
+    }
+
+    /// Replace the amount of stocks traded
+    pub fn with_amount(self, amount: f64) -> Result<Self, UnsupportedField> {
+        match self {
+            AccountEvent::StocksBought(event) => {
+                Ok(AccountEvent::StocksBought(StocksBought { amount, ..event }))
+            }
+            AccountEvent::StocksSold(event) => {
+                Ok(AccountEvent::StocksSold(StocksSold { amount, ..event }))
+            }
+            event => Err(UnsupportedField::new(&event, "amount")),
+        }
+    }
+
+    /// Whether the event corrects an earlier event, rather than recording something that happened
+    pub fn is_correction(&self) -> bool {
+        matches!(
+            self,
+            AccountEvent::EventVoided(_) | AccountEvent::EventAmended(_)
+        )
+    }
+
+    /// The id of the event that this corrects, None when it is no correction
+    pub fn corrected_event_id(&self) -> Option<Uuid> {
+        match self {
+            AccountEvent::EventVoided(event) => Some(event.event_id),
+            AccountEvent::EventAmended(event) => Some(event.event_id),
+            _ => None,
+        }
+    }
+
+    /// Attach the fee paid to the broker to a trade
+    pub fn with_fee(self, fee: String) -> Result<Self, UnsupportedField> {
+        let fee = Some(Amount::from(fee));
+        match self {
+            AccountEvent::StocksBought(event) => {
+                Ok(AccountEvent::StocksBought(StocksBought { fee, ..event }))
+            }
+            AccountEvent::StocksSold(event) => {
+                Ok(AccountEvent::StocksSold(StocksSold { fee, ..event }))
+            }
+            event => Err(UnsupportedField::new(&event, "fee")),
+        }
+    }
+
+    /// Attach the tax withheld per stock, and the country withholding it, to a dividend
+    pub fn with_withheld_tax(
+        self,
+        tax: String,
+        country: Option<String>,
+    ) -> Result<Self, UnsupportedField> {
+        match self {
+            AccountEvent::DividendPaid(event) => Ok(AccountEvent::DividendPaid(DividendPaid {
+                withheld_tax: Some(Amount::from(tax)),
+                country,
+                ..event
+            })),
+            event => Err(UnsupportedField::new(&event, "withheld tax")),
+        }
+    }
+
+    /// The name of the variant, as stored next to the event
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            AccountEvent::StocksBought(_) => "StocksBought",
+            AccountEvent::StocksSold(_) => "StocksSold",
+            AccountEvent::PriceObtained(_) => "PriceObtained",
+            AccountEvent::DividendPaid(_) => "DividendPaid",
+            AccountEvent::StockSplit(_) => "StockSplit",
+            AccountEvent::CashDeposited(_) => "CashDeposited",
+            AccountEvent::CashWithdrawn(_) => "CashWithdrawn",
+            AccountEvent::FeeCharged(_) => "FeeCharged",
+            AccountEvent::ExchangeRateObtained(_) => "ExchangeRateObtained",
+            AccountEvent::AccountOpened(_) => "AccountOpened",
+            AccountEvent::EventVoided(_) => "EventVoided",
+            AccountEvent::EventAmended(_) => "EventAmended",
+        }
+    }
+
+    pub fn created_at(&self) -> NaiveDateTime {
         match self {
             AccountEvent::StocksBought(event) => event.created_at,
+            AccountEvent::StocksSold(event) => event.created_at,
             AccountEvent::PriceObtained(event) => event.created_at,
             AccountEvent::DividendPaid(event) => event.created_at,
+            AccountEvent::StockSplit(event) => event.created_at,
+            AccountEvent::CashDeposited(event) => event.created_at,
+            AccountEvent::CashWithdrawn(event) => event.created_at,
+            AccountEvent::FeeCharged(event) => event.created_at,
+            AccountEvent::ExchangeRateObtained(event) => event.created_at,
+            AccountEvent::AccountOpened(event) => event.created_at,
+            AccountEvent::EventVoided(event) => event.created_at,
+            AccountEvent::EventAmended(event) => event.created_at,
+        }
+    }
+
+    /// The stock the event is about. None for events about the account or its cash.
+    pub fn identifier(&self) -> Option<&StockIdentifier> {
+        match self {
+            AccountEvent::StocksBought(event) => Some(&event.identifier),
+            AccountEvent::StocksSold(event) => Some(&event.identifier),
+            AccountEvent::PriceObtained(event) => Some(&event.identifier),
+            AccountEvent::DividendPaid(event) => Some(&event.identifier),
+            AccountEvent::StockSplit(event) => Some(&event.identifier),
+            AccountEvent::CashDeposited(_) => None,
+            AccountEvent::CashWithdrawn(_) => None,
+            AccountEvent::FeeCharged(_) => None,
+            AccountEvent::ExchangeRateObtained(_) => None,
+            AccountEvent::AccountOpened(_) => None,
+            AccountEvent::EventVoided(_) => None,
+            AccountEvent::EventAmended(_) => None,
+        }
+    }
+}
+
+/// A field was given for an event that does not have it, like the price of a split
+#[derive(Debug, PartialEq)]
+pub struct UnsupportedField {
+    pub event_type: &'static str,
+    pub field: &'static str,
+}
+
+impl UnsupportedField {
+    fn new(event: &AccountEvent, field: &'static str) -> Self {
+        Self {
+            event_type: event.event_type(),
+            field,
+        }
+    }
+}
+
+impl Error for UnsupportedField {}
+
+impl Display for UnsupportedField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} events have no {}", self.event_type, self.field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::date_utils::fixtures::iphone_launched_at;
+
+    use super::*;
+
+    #[test]
+    fn test_stocks_bought_total_includes_fee() {
+        let event = StocksBought {
+            fee: Some(Amount::from("5.00 USD")),
+            ..StocksBought::new(
+                iphone_launched_at(),
+                10.0,
+                "10.00 USD".to_string(),
+                "AAPL".to_string(),
+            )
+        };
+
+        assert_eq!(event.total(), Amount::from("105.00 USD"));
+        assert_eq!(event.price_including_fee(), Amount::from("10.50 USD"));
+    }
+
+    #[test]
+    fn test_stocks_sold_total_deducts_fee() {
+        let event = StocksSold {
+            fee: Some(Amount::from("5.00 USD")),
+            ..StocksSold::new(
+                iphone_launched_at(),
+                10.0,
+                "10.00 USD".to_string(),
+                "AAPL".to_string(),
+            )
+        };
+
+        assert_eq!(event.total(), Amount::from("95.00 USD"));
+    }
+
+    #[test]
+    fn test_stocks_bought_without_fee_deserializes() {
+        let json = r#"{"StocksBought":{"created_at":"2007-01-09T09:42:00","amount":10.0,"price":{"num":"10.00","currency":"USD"},"identifier":{"ticker":"AAPL"}}}"#;
+        let event: AccountEvent = serde_json::from_str(json).unwrap();
+
+        match event {
+            AccountEvent::StocksBought(event) => assert_eq!(event.fee, None),
+            _ => panic!("Unexpected event type"),
         }
     }
+
+    #[test]
+    fn test_with_withheld_tax() {
+        let event = AccountEvent::new_dividend_paid(
+            iphone_launched_at(),
+            "1.00 USD".to_string(),
+            "AAPL".to_string(),
+        )
+        .with_withheld_tax("0.15 USD".to_string(), Some("US".to_string()))
+        .unwrap();
+
+        match event {
+            AccountEvent::DividendPaid(event) => {
+                assert_eq!(event.withheld_tax(), Amount::from("0.15 USD"));
+                assert_eq!(event.country, Some("US".to_string()));
+            }
+            _ => panic!("Unexpected event type"),
+        }
+    }
+
+    #[test]
+    fn test_with_price_of_a_split_is_rejected() {
+        let split = AccountEvent::new_stock_split(
+            iphone_launched_at(),
+            "2:1".to_string(),
+            "AAPL".to_string(),
+        );
+
+        assert_eq!(
+            split.with_price("10.00 USD".to_string()).unwrap_err(),
+            UnsupportedField {
+                event_type: "StockSplit",
+                field: "price"
+            }
+        );
+    }
+
+    #[test]
+    fn test_with_amount_of_a_deposit_is_rejected() {
+        let deposit =
+            AccountEvent::new_cash_deposited(iphone_launched_at(), "100.00 USD".to_string());
+
+        assert_eq!(
+            deposit.with_amount(10.0).unwrap_err().to_string(),
+            "CashDeposited events have no amount"
+        );
+    }
+
+    #[test]
+    fn test_with_withheld_tax_on_a_trade_is_rejected() {
+        let buy = AccountEvent::new_stocks_bought(
+            iphone_launched_at(),
+            10.0,
+            "10.00 USD".to_string(),
+            "AAPL".to_string(),
+        );
+
+        assert_eq!(
+            buy.with_withheld_tax("0.15 USD".to_string(), None)
+                .unwrap_err()
+                .to_string(),
+            "StocksBought events have no withheld tax"
+        );
+    }
+
+    #[test]
+    fn test_with_fee() {
+        let event = AccountEvent::new_stocks_sold(
+            iphone_launched_at(),
+            10.0,
+            "10.00 USD".to_string(),
+            "AAPL".to_string(),
+        )
+        .with_fee("1.00 USD".to_string())
+        .unwrap();
+
+        match event {
+            AccountEvent::StocksSold(event) => {
+                assert_eq!(event.fee, Some(Amount::from("1.00 USD")))
+            }
+            _ => panic!("Unexpected event type"),
+        }
+    }
+
+    #[test]
+    fn test_with_fee_on_a_deposit_is_rejected() {
+        let deposit =
+            AccountEvent::new_cash_deposited(iphone_launched_at(), "100.00 USD".to_string());
+
+        assert_eq!(
+            deposit.with_fee("1.00 USD".to_string()).unwrap_err(),
+            UnsupportedField {
+                event_type: "CashDeposited",
+                field: "fee"
+            }
+        );
+    }
 }
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::value_objects::{Amount, Amounts, Currency};
+
+/// All exchange rates obtained over time, per currency pair
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeRates {
+    /// Rates per (from, to) pair, sorted by the time they were obtained
+    #[serde(with = "crate::snapshot::map_as_pairs")]
+    rates: HashMap<(Currency, Currency), Vec<(NaiveDateTime, Decimal)>>,
+}
+
+impl ExchangeRates {
+    /// Record that one unit of `from` costs `rate` units of `to` from `at` onwards
+    pub fn record(&mut self, from: Currency, to: Currency, at: NaiveDateTime, rate: Decimal) {
+        let rates = self.rates.entry((from, to)).or_default();
+        let position = rates.partition_point(|(obtained_at, _)| *obtained_at <= at);
+        rates.insert(position, (at, rate));
+    }
+
+    /// The rate in effect at the given time. Looks up the inverse pair when
+    /// only that was obtained. None means that no rate was known at that time.
+    pub fn rate_on(&self, from: &Currency, to: &Currency, at: NaiveDateTime) -> Option<Decimal> {
+        if from == to {
+            return Some(Decimal::ONE);
+        }
+
+        let in_effect = |key: (Currency, Currency)| {
+            self.rates.get(&key).and_then(|rates| {
+                rates
+                    .iter()
+                    .rev()
+                    .find(|(obtained_at, _)| *obtained_at <= at)
+                    .map(|(_, rate)| *rate)
+            })
+        };
+
+        in_effect((from.clone(), to.clone())).or_else(|| {
+            in_effect((to.clone(), from.clone()))
+                .filter(|rate| !rate.is_zero())
+                .map(|rate| Decimal::ONE / rate)
+        })
+    }
+
+    /// The last rate obtained
+    pub fn latest_rate(&self, from: &Currency, to: &Currency) -> Option<Decimal> {
+        self.rate_on(from, to, NaiveDateTime::MAX)
+    }
+
+    /// Convert the amount at the rate in effect at the given time
+    pub fn convert_on(&self, amount: &Amount, to: &Currency, at: NaiveDateTime) -> Option<Amount> {
+        self.rate_on(&amount.currency, to, at)
+            .map(|rate| Amount::new(amount.num * rate, to.clone()))
+    }
+
+    /// Convert the amount at the last rate obtained
+    pub fn convert(&self, amount: &Amount, to: &Currency) -> Option<Amount> {
+        self.convert_on(amount, to, NaiveDateTime::MAX)
+    }
+
+    /// Add up amounts in several currencies into one amount, at the last rates obtained
+    pub fn consolidate(&self, amounts: &Amounts, to: &Currency) -> Consolidated {
+        let mut consolidated = Consolidated {
+            total: Amount::zero(to.clone()),
+            missing_rates: vec![],
+        };
+
+        for amount in amounts.sorted().iter().filter(|amt| !amt.num.is_zero()) {
+            match self.convert(amount, to) {
+                Some(converted) => consolidated.total += converted,
+                None => consolidated.missing_rates.push(amount.currency.clone()),
+            }
+        }
+
+        consolidated
+    }
+}
+
+/// A total in one currency, with the currencies that could not be converted
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Consolidated {
+    /// The sum of all amounts that could be converted
+    pub total: Amount,
+    /// The currencies left out of the total, because no rate is known
+    pub missing_rates: Vec<Currency>,
+}
+
+impl Consolidated {
+    pub fn is_complete(&self) -> bool {
+        self.missing_rates.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::date_utils::fixtures::iphone_launched_at;
+
+    use super::*;
+
+    #[test]
+    fn test_rate_on_same_currency() {
+        let rates = ExchangeRates::default();
+        assert_eq!(
+            rates.rate_on(&"EUR".into(), &"EUR".into(), iphone_launched_at()),
+            Some(Decimal::ONE)
+        );
+    }
+
+    #[test]
+    fn test_rate_on_uses_rate_in_effect() {
+        let rates = rates();
+        let day = chrono::Duration::days(1);
+
+        assert_eq!(
+            rates.rate_on(&"USD".into(), &"EUR".into(), iphone_launched_at() - day),
+            None
+        );
+        assert_eq!(
+            rates.rate_on(&"USD".into(), &"EUR".into(), iphone_launched_at()),
+            Some(Decimal::new(80, 2))
+        );
+        assert_eq!(
+            rates.rate_on(&"USD".into(), &"EUR".into(), iphone_launched_at() + day),
+            Some(Decimal::new(50, 2))
+        );
+    }
+
+    #[test]
+    fn test_rate_on_uses_inverse_rate() {
+        let rates = rates();
+        assert_eq!(
+            rates.latest_rate(&"EUR".into(), &"USD".into()),
+            Some(Decimal::TWO)
+        );
+    }
+
+    #[test]
+    fn test_consolidate_flags_missing_rates() {
+        let rates = rates();
+        let amounts = Amounts::new(vec![
+            Amount::from("10.00 USD"),
+            Amount::from("5.00 EUR"),
+            Amount::from("100.00 JPY"),
+        ]);
+
+        assert_eq!(
+            rates.consolidate(&amounts, &"EUR".into()),
+            Consolidated {
+                total: Amount::from("10.00 EUR"),
+                missing_rates: vec!["JPY".into()],
+            }
+        );
+    }
+
+    fn rates() -> ExchangeRates {
+        let mut rates = ExchangeRates::default();
+        rates.record(
+            "USD".into(),
+            "EUR".into(),
+            iphone_launched_at() + chrono::Duration::days(1),
+            Decimal::new(50, 2),
+        );
+        rates.record(
+            "USD".into(),
+            "EUR".into(),
+            iphone_launched_at(),
+            Decimal::new(80, 2),
+        );
+        rates
+    }
+}
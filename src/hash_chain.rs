@@ -0,0 +1,207 @@
+use std::{error::Error, fmt::Display};
+
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::envelope::EventEnvelope;
+
+/// The hash of the event, chained to the hash of the event stored before it in the
+/// same aggregate. Changing, removing or reordering a stored event breaks the chain.
+///
+/// The event is hashed in a canonical form, with the fields sorted and the empty
+/// ones left out. Adding an optional field to an event does not change the hash of
+/// the events stored before it, whether they are read as stored or migrated.
+pub fn chain_hash(envelope: &EventEnvelope, previous_hash: Option<&str>) -> String {
+    canonical_hash(json!([
+        previous_hash,
+        envelope.id,
+        envelope.sequence,
+        envelope.event_type,
+        envelope.recorded_at,
+        envelope.source,
+        envelope.comment,
+        envelope.event,
+    ]))
+}
+
+fn canonical_hash(content: Value) -> String {
+    // The keys of a serde_json map are sorted
+    sha256_hex(without_nulls(content).to_string().as_bytes())
+}
+
+/// Leave out the fields of objects that are null. Nulls in lists keep their place.
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, without_nulls(value)))
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(without_nulls).collect()),
+        value => value,
+    }
+}
+
+pub(crate) fn sha256_hex(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// The last link of an intact chain
+#[derive(Debug, PartialEq)]
+pub struct ChainHead {
+    /// The number of events in the chain
+    pub events: usize,
+    /// The hash of the last event, to write down and compare with later.
+    /// None when there are no events.
+    pub hash: Option<String>,
+}
+
+/// The first event whose stored hash does not match its content and the event before it
+#[derive(Debug, PartialEq)]
+pub struct BrokenLink {
+    pub sequence: u64,
+    pub id: Uuid,
+}
+
+impl Error for BrokenLink {}
+
+impl Display for BrokenLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the chain is broken at event {} ({})",
+            self.sequence, self.id
+        )
+    }
+}
+
+/// Walk the events of an aggregate in the order they were stored, and check every link
+pub fn verify_chain(mut envelopes: Vec<EventEnvelope>) -> Result<ChainHead, BrokenLink> {
+    envelopes.sort_by_key(|envelope| envelope.sequence);
+
+    let mut previous_hash: Option<String> = None;
+    for envelope in &envelopes {
+        let expected = chain_hash(envelope, previous_hash.as_deref());
+        if envelope.hash.as_deref() != Some(expected.as_str()) {
+            return Err(BrokenLink {
+                sequence: envelope.sequence,
+                id: envelope.id,
+            });
+        }
+        previous_hash = envelope.hash.clone();
+    }
+
+    Ok(ChainHead {
+        events: envelopes.len(),
+        hash: previous_hash,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{date_utils::fixtures::iphone_launched_at, events::AccountEvent};
+
+    use super::*;
+
+    #[test]
+    fn test_verify_intact_chain() {
+        let envelopes = chained(3);
+        let head = verify_chain(envelopes.clone()).unwrap();
+
+        assert_eq!(
+            head,
+            ChainHead {
+                events: 3,
+                hash: envelopes[2].hash.clone()
+            }
+        );
+    }
+
+    #[test]
+    fn test_verify_changed_event() {
+        let mut envelopes = chained(3);
+        envelopes[1].event =
+            AccountEvent::new_cash_deposited(iphone_launched_at(), "1000.00 USD".to_string());
+
+        assert_eq!(
+            verify_chain(envelopes.clone()),
+            Err(BrokenLink {
+                sequence: 2,
+                id: envelopes[1].id
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_removed_event() {
+        let mut envelopes = chained(3);
+        let removed = envelopes.remove(1);
+
+        assert_eq!(
+            verify_chain(envelopes.clone()),
+            Err(BrokenLink {
+                sequence: 3,
+                id: envelopes[1].id
+            })
+        );
+        assert_ne!(removed.hash, None);
+    }
+
+    #[test]
+    fn test_hash_of_an_event_stored_before_a_field_was_added() {
+        // Stored without the fee, which was added later as an optional field
+        let stored = r#"{"StocksBought":{"created_at":"2007-01-09T09:42:00","amount":10.0,"price":{"num":"10.00","currency":"USD"},"identifier":{"ticker":"AAPL"}}}"#;
+        let mut envelope = EventEnvelope::new(serde_json::from_str(stored).unwrap());
+        envelope.sequence = 1;
+        let hashed_as_stored = canonical_hash(json!([
+            None::<String>,
+            envelope.id,
+            envelope.sequence,
+            envelope.event_type,
+            envelope.recorded_at,
+            envelope.source,
+            envelope.comment,
+            serde_json::from_str::<Value>(stored).unwrap(),
+        ]));
+        envelope.hash = Some(hashed_as_stored);
+
+        assert!(serde_json::to_string(&envelope.event)
+            .unwrap()
+            .contains(r#""fee":null"#));
+        assert_eq!(verify_chain(vec![envelope]).unwrap().events, 1);
+    }
+
+    #[test]
+    fn test_verify_no_events() {
+        assert_eq!(
+            verify_chain(vec![]),
+            Ok(ChainHead {
+                events: 0,
+                hash: None
+            })
+        );
+    }
+
+    /// Envelopes as the event store chains them
+    fn chained(count: u64) -> Vec<EventEnvelope> {
+        let mut previous_hash: Option<String> = None;
+        (1..=count)
+            .map(|sequence| {
+                let mut envelope = EventEnvelope::new(AccountEvent::new_cash_deposited(
+                    iphone_launched_at(),
+                    "1.00 USD".to_string(),
+                ));
+                envelope.sequence = sequence;
+                envelope.hash = Some(chain_hash(&envelope, previous_hash.as_deref()));
+                previous_hash = envelope.hash.clone();
+                envelope
+            })
+            .collect()
+    }
+}
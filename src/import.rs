@@ -0,0 +1,474 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::Display,
+    io::Read,
+    str::FromStr,
+};
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::{
+    date_utils::{now, parse_datetime_or},
+    events::AccountEvent,
+    value_objects::StockIdentifier,
+};
+
+/// The kind of event a row of a broker export turns into
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportType {
+    Buy,
+    Dividend,
+    Price,
+}
+
+/// How the columns of a broker export map onto events.
+/// Read from JSON, missing keys fall back to the bullboard defaults.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ImportProfile {
+    /// The column holding the date of the event
+    pub date: String,
+    /// The chrono format of the dates, e.g. %d-%m-%Y
+    pub date_format: String,
+    /// The column holding the type of the event
+    #[serde(rename = "type")]
+    pub rtype: String,
+    /// The column holding the ticker
+    pub ticker: String,
+    /// The column holding the number of stocks. Not used for dividends and prices
+    pub amount: String,
+    /// The column holding the price per stock, or the dividend per stock
+    pub price: String,
+    /// The column holding the currency of the price
+    pub currency: String,
+    /// The values in the type column, and the event each turns into.
+    /// Rows with any other value are skipped.
+    pub types: HashMap<String, ImportType>,
+    pub delimiter: char,
+}
+
+impl Default for ImportProfile {
+    fn default() -> Self {
+        Self {
+            date: "Date".to_string(),
+            date_format: "%Y-%m-%d".to_string(),
+            rtype: "Type".to_string(),
+            ticker: "Ticker".to_string(),
+            amount: "Amount".to_string(),
+            price: "Price".to_string(),
+            currency: "Currency".to_string(),
+            types: HashMap::from([
+                ("buy".to_string(), ImportType::Buy),
+                ("dividend".to_string(), ImportType::Dividend),
+                ("price".to_string(), ImportType::Price),
+            ]),
+            delimiter: ',',
+        }
+    }
+}
+
+impl FromStr for ImportProfile {
+    type Err = ImportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(serde_json::from_str(s)?)
+    }
+}
+
+/// The events read from a broker export
+#[derive(Debug)]
+pub struct Import {
+    pub events: Vec<AccountEvent>,
+    /// The number of rows with a type that is not in the profile
+    pub skipped_rows: usize,
+}
+
+impl Import {
+    /// Read all rows of the CSV, mapping the columns through the profile
+    pub fn from_csv(reader: impl Read, profile: &ImportProfile) -> Result<Self, ImportError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(profile.delimiter as u8)
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+
+        let headers = reader.headers()?.clone();
+        let column = |name: &str| {
+            headers
+                .iter()
+                .position(|header| header == name)
+                .ok_or(ImportError::MissingColumn(name.to_string()))
+        };
+        let date = column(&profile.date)?;
+        let rtype = column(&profile.rtype)?;
+        let ticker = column(&profile.ticker)?;
+        let price = column(&profile.price)?;
+        let currency = column(&profile.currency)?;
+        // Exports with only dividends or prices need no amount column
+        let amount = column(&profile.amount).ok();
+
+        let mut import = Import {
+            events: vec![],
+            skipped_rows: 0,
+        };
+        for (index, record) in reader.records().enumerate() {
+            let record = record?;
+            // The header is line 1
+            let line = index + 2;
+            let field = |column: usize| record.get(column).unwrap_or_default();
+            let invalid = |column: &str, value: &str| ImportError::InvalidValue {
+                line,
+                column: column.to_string(),
+                value: value.to_string(),
+            };
+
+            let Some(import_type) = profile.types.get(field(rtype)) else {
+                import.skipped_rows += 1;
+                continue;
+            };
+
+            let created_at = NaiveDate::parse_from_str(field(date), &profile.date_format)
+                .map_err(|_| invalid(&profile.date, field(date)))?
+                .and_hms_opt(0, 0, 0)
+                .unwrap();
+            Decimal::from_str(field(price)).map_err(|_| invalid(&profile.price, field(price)))?;
+            if !is_currency(field(currency)) {
+                return Err(invalid(&profile.currency, field(currency)));
+            }
+            let price_s = format!("{} {}", field(price), field(currency));
+            let ticker_s = field(ticker).to_string();
+
+            let event = match import_type {
+                ImportType::Buy => {
+                    let amount_s = amount.map(field).unwrap_or_default();
+                    let amount = amount_s
+                        .parse::<f64>()
+                        .map_err(|_| invalid(&profile.amount, amount_s))?;
+                    AccountEvent::new_stocks_bought(created_at, amount, price_s, ticker_s)
+                }
+                ImportType::Dividend => {
+                    AccountEvent::new_dividend_paid(created_at, price_s, ticker_s)
+                }
+                ImportType::Price => {
+                    AccountEvent::new_price_obtained(created_at, price_s, ticker_s)
+                }
+            };
+            import.events.push(event);
+        }
+
+        Ok(import)
+    }
+
+    /// Read a price history, e.g. one downloaded from a market data site, with the
+    /// columns date, ticker, close and currency
+    pub fn from_price_history_csv(reader: impl Read) -> Result<Self, ImportError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+
+        let headers = reader.headers()?.clone();
+        let column = |name: &str| {
+            headers
+                .iter()
+                .position(|header| header.eq_ignore_ascii_case(name))
+                .ok_or(ImportError::MissingColumn(name.to_string()))
+        };
+        let date = column("date")?;
+        let ticker = column("ticker")?;
+        let close = column("close")?;
+        let currency = column("currency")?;
+
+        let mut import = Import {
+            events: vec![],
+            skipped_rows: 0,
+        };
+        for (index, record) in reader.records().enumerate() {
+            let record = record?;
+            let line = index + 2;
+            let field = |column: usize| record.get(column).unwrap_or_default();
+            let invalid = |column: &str, value: &str| ImportError::InvalidValue {
+                line,
+                column: column.to_string(),
+                value: value.to_string(),
+            };
+
+            let created_at = parse_datetime_or(Some(field(date).to_string()), now)
+                .map_err(|_| invalid("date", field(date)))?;
+            Decimal::from_str(field(close)).map_err(|_| invalid("close", field(close)))?;
+            if !is_currency(field(currency)) {
+                return Err(invalid("currency", field(currency)));
+            }
+
+            import.events.push(AccountEvent::new_price_obtained(
+                created_at,
+                format!("{} {}", field(close), field(currency)),
+                field(ticker).to_string(),
+            ));
+        }
+
+        Ok(import)
+    }
+
+    /// Leave out the prices for a stock on a date that already has a price, in the store
+    /// or earlier in the import. Returns the number of prices left out.
+    pub fn remove_known_prices(&mut self, existing: &[AccountEvent]) -> usize {
+        let mut known: HashSet<(StockIdentifier, NaiveDate)> = existing
+            .iter()
+            .filter_map(|event| match event {
+                AccountEvent::PriceObtained(props) => {
+                    Some((props.identifier.clone(), props.created_at.date()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let before = self.events.len();
+        self.events.retain(|event| match event {
+            AccountEvent::PriceObtained(props) => {
+                known.insert((props.identifier.clone(), props.created_at.date()))
+            }
+            _ => true,
+        });
+        before - self.events.len()
+    }
+
+    /// Leave out the events that are already stored, so importing the same file twice
+    /// does not count them twice. Returns the number of duplicates left out.
+    pub fn remove_duplicates(&mut self, existing: &[AccountEvent]) -> usize {
+        // Count identical events, so that two identical trades on one day both count
+        let mut stored: HashMap<String, usize> = HashMap::new();
+        for event in existing {
+            *stored.entry(fingerprint(event)).or_default() += 1;
+        }
+
+        let before = self.events.len();
+        self.events
+            .retain(|event| match stored.get_mut(&fingerprint(event)) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            });
+        before - self.events.len()
+    }
+}
+
+/// A currency or commodity is a single word, e.g. USD
+fn is_currency(value: &str) -> bool {
+    !value.is_empty() && !value.contains(char::is_whitespace)
+}
+
+fn fingerprint(event: &AccountEvent) -> String {
+    serde_json::to_string(event).expect("events can be serialized")
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    MissingColumn(String),
+    InvalidValue {
+        line: usize,
+        column: String,
+        value: String,
+    },
+    CsvError(String),
+    ProfileError(String),
+}
+impl Error for ImportError {}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::MissingColumn(column) => write!(f, "Column {} not found", column),
+            ImportError::InvalidValue {
+                line,
+                column,
+                value,
+            } => write!(f, "Invalid {} '{}' on line {}", column, value, line),
+            ImportError::CsvError(msg) => write!(f, "CSV error: {}", msg),
+            ImportError::ProfileError(msg) => write!(f, "Profile error: {}", msg),
+        }
+    }
+}
+
+impl From<csv::Error> for ImportError {
+    fn from(err: csv::Error) -> Self {
+        ImportError::CsvError(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ImportError {
+    fn from(err: serde_json::Error) -> Self {
+        ImportError::ProfileError(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::date_utils::fixtures::iphone_launched_at;
+
+    use super::*;
+
+    const EXPORT: &str = "Date,Type,Ticker,Amount,Price,Currency
+2007-01-09,buy,AAPL,10,85.00,USD
+2007-01-10,deposit,,,1000.00,USD
+2007-01-11,dividend,AAPL,,0.10,USD
+2007-01-12,price,AAPL,,90.00,USD
+";
+
+    #[test]
+    fn test_from_csv_with_default_profile() {
+        let import = Import::from_csv(EXPORT.as_bytes(), &ImportProfile::default()).unwrap();
+
+        assert_eq!(import.skipped_rows, 1);
+        assert_eq!(
+            import
+                .events
+                .iter()
+                .map(fingerprint)
+                .collect::<Vec<String>>(),
+            vec![
+                fingerprint(&AccountEvent::new_stocks_bought(
+                    iphone_launched_at().date().and_hms_opt(0, 0, 0).unwrap(),
+                    10.0,
+                    "85.00 USD".to_string(),
+                    "AAPL".to_string(),
+                )),
+                fingerprint(&AccountEvent::new_dividend_paid(
+                    NaiveDate::from_ymd_opt(2007, 1, 11)
+                        .unwrap()
+                        .and_hms_opt(0, 0, 0)
+                        .unwrap(),
+                    "0.10 USD".to_string(),
+                    "AAPL".to_string(),
+                )),
+                fingerprint(&AccountEvent::new_price_obtained(
+                    NaiveDate::from_ymd_opt(2007, 1, 12)
+                        .unwrap()
+                        .and_hms_opt(0, 0, 0)
+                        .unwrap(),
+                    "90.00 USD".to_string(),
+                    "AAPL".to_string(),
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_csv_with_custom_profile() {
+        let profile: ImportProfile = r#"{
+            "date": "Datum",
+            "date_format": "%d-%m-%Y",
+            "type": "Actie",
+            "ticker": "Product",
+            "amount": "Aantal",
+            "price": "Koers",
+            "currency": "Valuta",
+            "types": {"Koop": "buy"},
+            "delimiter": ";"
+        }"#
+        .parse()
+        .unwrap();
+        let export = "Datum;Actie;Product;Aantal;Koers;Valuta\n09-01-2007;Koop;AAPL;10;85,00;USD\n";
+
+        let err = Import::from_csv(export.as_bytes(), &profile).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid Koers '85,00' on line 2".to_string()
+        );
+
+        let export = export.replace("85,00", "85.00");
+        let import = Import::from_csv(export.as_bytes(), &profile).unwrap();
+        assert_eq!(import.events.len(), 1);
+    }
+
+    #[test]
+    fn test_from_csv_without_required_column() {
+        let err =
+            Import::from_csv("Date,Type\n".as_bytes(), &ImportProfile::default()).unwrap_err();
+        assert_eq!(err.to_string(), "Column Ticker not found".to_string());
+    }
+
+    #[test]
+    fn test_from_csv_without_currency() {
+        let csv = "Date,Type,Ticker,Amount,Price,Currency
+2021-10-01,buy,MSFT,5,60.00,
+";
+        let err = Import::from_csv(csv.as_bytes(), &ImportProfile::default()).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid Currency '' on line 2".to_string());
+    }
+
+    #[test]
+    fn test_from_price_history_csv() {
+        let history = "Date,Ticker,Close,Currency
+2007-01-09,AAPL,92.70,USD
+10-01-2007,AAPL,97.00,USD
+";
+        let import = Import::from_price_history_csv(history.as_bytes()).unwrap();
+        assert_eq!(import.events.len(), 2);
+        assert_eq!(
+            import.events[1].created_at(),
+            NaiveDate::from_ymd_opt(2007, 1, 10)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        );
+
+        let err = Import::from_price_history_csv(
+            "date,ticker,close,currency
+2007-01-09,AAPL,n/a,USD
+"
+            .as_bytes(),
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Invalid close 'n/a' on line 2".to_string());
+
+        let err = Import::from_price_history_csv(
+            "date,ticker,close,currency
+2007-01-09,AAPL,92.70,
+"
+            .as_bytes(),
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Invalid currency '' on line 2".to_string());
+    }
+
+    #[test]
+    fn test_remove_known_prices() {
+        let history = "date,ticker,close,currency
+2007-01-09,AAPL,92.70,USD
+2007-01-09,AAPL,92.80,USD
+2007-01-10,AAPL,97.00,USD
+2007-01-10,MSFT,29.66,USD
+";
+        let existing = vec![AccountEvent::new_price_obtained(
+            iphone_launched_at(),
+            "90.00 USD".to_string(),
+            "AAPL".to_string(),
+        )];
+        let mut import = Import::from_price_history_csv(history.as_bytes()).unwrap();
+
+        assert_eq!(import.remove_known_prices(&existing), 2);
+        assert_eq!(import.events.len(), 2);
+    }
+
+    #[test]
+    fn test_remove_duplicates() {
+        let export = "Date,Type,Ticker,Amount,Price,Currency
+2007-01-09,buy,AAPL,10,85.00,USD
+2007-01-09,buy,AAPL,10,85.00,USD
+2007-01-11,dividend,AAPL,,0.10,USD
+";
+        let stored = Import::from_csv(export.as_bytes(), &ImportProfile::default())
+            .unwrap()
+            .events;
+        let mut import = Import::from_csv(export.as_bytes(), &ImportProfile::default()).unwrap();
+
+        // Only one of the two identical buys was stored before
+        assert_eq!(import.remove_duplicates(&stored[1..]), 2);
+        assert_eq!(import.events.len(), 1);
+    }
+}
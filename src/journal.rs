@@ -1,63 +1,211 @@
 use chrono::NaiveDate;
+use serde::Serialize;
 
 use crate::{
     events::AccountEvent,
-    value_objects::{Amount, StockIdentifier},
+    exchange_rates::ExchangeRates,
+    value_objects::{Amount, Currency, StockIdentifier},
 };
 
 #[derive(PartialEq, Debug)]
 pub enum JournalEntry {
     Buy(JournalRow),
+    Sell(JournalRow),
     Dividend(JournalRow),
-    // TODO: Split, Merge, Top-up, Withdraw, Tax, Interest, Fee, claim-event, etc.
+    Split(JournalRow),
+    Deposit(JournalRow),
+    Withdrawal(JournalRow),
+    Fee(JournalRow),
+    /// An earlier entry was voided
+    Void(JournalRow),
+    /// An earlier entry was amended, with the new values
+    Amend(JournalRow),
+    // TODO: Merge, Tax, Interest, claim-event, etc.
 }
 
 pub struct Journal {
     pub entries: Vec<JournalEntry>,
+    exchange_rates: ExchangeRates,
+    /// The currency to convert the totals to
+    base_currency: Option<Currency>,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Serialize)]
 pub enum JournalRowType {
     Buy,
+    Sell,
     Dividend,
+    Split,
+    Deposit,
+    Withdrawal,
+    Fee,
+    Void,
+    Amend,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Serialize)]
 pub struct JournalRow {
     pub date: Option<NaiveDate>,
     pub rtype: JournalRowType,
     pub identifier: StockIdentifier,
     pub amount: f64,
-    pub price: Amount,
-    pub total: Amount,
+    /// The price and the total are None for splits, which have neither
+    pub price: Option<Amount>,
+    pub total: Option<Amount>,
 }
 
 impl Journal {
     pub fn new(events: Vec<AccountEvent>) -> Self {
         let entries = events
             .iter()
-            .filter_map(|event| match event {
-                AccountEvent::StocksBought(props) => Some(JournalEntry::Buy(JournalRow {
-                    date: Some(props.created_at.date()),
-                    rtype: JournalRowType::Buy,
-                    identifier: props.identifier.clone(),
-                    amount: props.amount,
-                    price: props.price.clone(),
-                    total: (props.price.clone() * props.amount),
-                })),
-                AccountEvent::DividendPaid(props) => Some(JournalEntry::Dividend(JournalRow {
-                    date: Some(props.created_at.date()),
-                    rtype: JournalRowType::Dividend,
-                    identifier: props.identifier.clone(),
-                    amount: 1.0, // TODO: Change dividend to have price per share instead of total
-                    price: props.price.clone(),
-                    total: props.price.clone(),
-                })),
-                AccountEvent::PriceObtained { .. } => None,
-            })
+            .filter_map(journal_entry)
             .collect::<Vec<JournalEntry>>();
 
-        Self { entries }
+        let mut exchange_rates = ExchangeRates::default();
+        for event in &events {
+            if let AccountEvent::ExchangeRateObtained(props) = event {
+                exchange_rates.record(
+                    props.currency.clone(),
+                    props.price.currency.clone(),
+                    props.created_at,
+                    props.price.num,
+                );
+            }
+        }
+
+        Self {
+            entries,
+            exchange_rates,
+            base_currency: None,
+        }
+    }
+
+    /// Convert the totals to the given currency, at the rate in effect on the date of each row
+    pub fn in_base_currency(mut self, base_currency: Currency) -> Self {
+        self.base_currency = Some(base_currency);
+        self
+    }
+
+    pub fn base_currency(&self) -> Option<&Currency> {
+        self.base_currency.as_ref()
+    }
+
+    /// The total of the row in the base currency, at the rate in effect at the end of its date.
+    /// None means that there is no base currency, or no rate was known on that date.
+    pub fn converted_total(&self, row: &JournalRow) -> Option<Amount> {
+        let base_currency = self.base_currency.as_ref()?;
+        let at = row.date?.and_hms_opt(23, 59, 59)?;
+        self.exchange_rates
+            .convert_on(row.total.as_ref()?, base_currency, at)
+    }
+}
+
+impl JournalEntry {
+    pub fn row(&self) -> &JournalRow {
+        match self {
+            JournalEntry::Buy(row)
+            | JournalEntry::Sell(row)
+            | JournalEntry::Dividend(row)
+            | JournalEntry::Split(row)
+            | JournalEntry::Deposit(row)
+            | JournalEntry::Withdrawal(row)
+            | JournalEntry::Fee(row)
+            | JournalEntry::Void(row)
+            | JournalEntry::Amend(row) => row,
+        }
+    }
+
+    fn into_row(self) -> JournalRow {
+        match self {
+            JournalEntry::Buy(row)
+            | JournalEntry::Sell(row)
+            | JournalEntry::Dividend(row)
+            | JournalEntry::Split(row)
+            | JournalEntry::Deposit(row)
+            | JournalEntry::Withdrawal(row)
+            | JournalEntry::Fee(row)
+            | JournalEntry::Void(row)
+            | JournalEntry::Amend(row) => row,
+        }
+    }
+}
+
+fn journal_entry(event: &AccountEvent) -> Option<JournalEntry> {
+    match event {
+        AccountEvent::StocksBought(props) => Some(JournalEntry::Buy(JournalRow {
+            date: Some(props.created_at.date()),
+            rtype: JournalRowType::Buy,
+            identifier: props.identifier.clone(),
+            amount: props.amount,
+            price: Some(props.price.clone()),
+            total: Some(props.total()),
+        })),
+        AccountEvent::StocksSold(props) => Some(JournalEntry::Sell(JournalRow {
+            date: Some(props.created_at.date()),
+            rtype: JournalRowType::Sell,
+            identifier: props.identifier.clone(),
+            amount: props.amount,
+            price: Some(props.price.clone()),
+            total: Some(props.total()),
+        })),
+        AccountEvent::DividendPaid(props) => Some(JournalEntry::Dividend(JournalRow {
+            date: Some(props.created_at.date()),
+            rtype: JournalRowType::Dividend,
+            identifier: props.identifier.clone(),
+            amount: 1.0, // TODO: Change dividend to have price per share instead of total
+            price: Some(props.price.clone()),
+            total: Some(props.price.clone()),
+        })),
+        AccountEvent::StockSplit(props) => Some(JournalEntry::Split(JournalRow {
+            date: Some(props.created_at.date()),
+            rtype: JournalRowType::Split,
+            identifier: props.identifier.clone(),
+            amount: props.ratio.factor(), // The number of new stocks per old stock
+            price: None,
+            total: None,
+        })),
+        AccountEvent::CashDeposited(props) => Some(JournalEntry::Deposit(JournalRow {
+            date: Some(props.created_at.date()),
+            rtype: JournalRowType::Deposit,
+            identifier: StockIdentifier::from(props.amount.currency.to_string()),
+            amount: 1.0,
+            price: Some(props.amount.clone()),
+            total: Some(props.amount.clone()),
+        })),
+        AccountEvent::CashWithdrawn(props) => Some(JournalEntry::Withdrawal(JournalRow {
+            date: Some(props.created_at.date()),
+            rtype: JournalRowType::Withdrawal,
+            identifier: StockIdentifier::from(props.amount.currency.to_string()),
+            amount: 1.0,
+            price: Some(props.amount.clone()),
+            total: Some(props.amount.clone()),
+        })),
+        AccountEvent::FeeCharged(props) => Some(JournalEntry::Fee(JournalRow {
+            date: Some(props.created_at.date()),
+            rtype: JournalRowType::Fee,
+            identifier: StockIdentifier::from(props.amount.currency.to_string()),
+            amount: 1.0,
+            price: Some(props.amount.clone()),
+            total: Some(props.amount.clone()),
+        })),
+        AccountEvent::PriceObtained { .. } => None,
+        AccountEvent::ExchangeRateObtained { .. } => None,
+        AccountEvent::AccountOpened { .. } => None,
+        // Corrections show the event they correct, on the date of the correction
+        AccountEvent::EventVoided(props) => journal_entry(&props.event).map(|entry| {
+            JournalEntry::Void(JournalRow {
+                date: Some(props.created_at.date()),
+                rtype: JournalRowType::Void,
+                ..entry.into_row()
+            })
+        }),
+        AccountEvent::EventAmended(props) => journal_entry(&props.event).map(|entry| {
+            JournalEntry::Amend(JournalRow {
+                date: Some(props.created_at.date()),
+                rtype: JournalRowType::Amend,
+                ..entry.into_row()
+            })
+        }),
     }
 }
 
@@ -94,21 +242,43 @@ mod tests {
                     rtype: JournalRowType::Buy,
                     identifier: StockIdentifier::from("AAPL"),
                     amount: 10.0,
-                    price: Amount::from("100.00 USD"),
-                    total: Amount::from("1000.00 USD")
+                    price: Some(Amount::from("100.00 USD")),
+                    total: Some(Amount::from("1000.00 USD"))
                 }),
                 JournalEntry::Buy(JournalRow {
                     date: Some(iphone_launched_at().date()),
                     rtype: JournalRowType::Buy,
                     identifier: StockIdentifier::from("AAPL"),
                     amount: 20.0,
-                    price: Amount::from("200.00 USD"),
-                    total: Amount::from("4000.00 USD")
+                    price: Some(Amount::from("200.00 USD")),
+                    total: Some(Amount::from("4000.00 USD"))
                 })
             ]
         );
     }
 
+    #[test]
+    fn journal_from_stocks_sold_events() {
+        let events = vec![AccountEvent::new_stocks_sold(
+            iphone_launched_at(),
+            10.0,
+            "100.00 USD".to_string(),
+            "AAPL".to_string(),
+        )];
+        let journal = Journal::new(events);
+        assert_eq!(
+            journal.entries,
+            vec![JournalEntry::Sell(JournalRow {
+                date: Some(iphone_launched_at().date()),
+                rtype: JournalRowType::Sell,
+                identifier: StockIdentifier::from("AAPL"),
+                amount: 10.0,
+                price: Some(Amount::from("100.00 USD")),
+                total: Some(Amount::from("1000.00 USD"))
+            })]
+        );
+    }
+
     #[test]
     fn journal_from_dividend_paid_events() {
         let events = vec![
@@ -132,21 +302,145 @@ mod tests {
                     rtype: JournalRowType::Dividend,
                     identifier: StockIdentifier::from("AAPL"),
                     amount: 1.0,
-                    price: Amount::from("100.00 USD"),
-                    total: Amount::from("100.00 USD")
+                    price: Some(Amount::from("100.00 USD")),
+                    total: Some(Amount::from("100.00 USD"))
                 }),
                 JournalEntry::Dividend(JournalRow {
                     date: Some(iphone_launched_at().date()),
                     rtype: JournalRowType::Dividend,
                     identifier: StockIdentifier::from("AAPL"),
                     amount: 1.0,
-                    price: Amount::from("200.00 USD"),
-                    total: Amount::from("200.00 USD")
+                    price: Some(Amount::from("200.00 USD")),
+                    total: Some(Amount::from("200.00 USD"))
                 })
             ]
         );
     }
 
+    #[test]
+    fn journal_from_stock_split_events() {
+        let events = vec![AccountEvent::new_stock_split(
+            iphone_launched_at(),
+            "4:1".to_string(),
+            "AAPL".to_string(),
+        )];
+        let journal = Journal::new(events);
+        assert_eq!(
+            journal.entries,
+            vec![JournalEntry::Split(JournalRow {
+                date: Some(iphone_launched_at().date()),
+                rtype: JournalRowType::Split,
+                identifier: StockIdentifier::from("AAPL"),
+                amount: 4.0,
+                price: None,
+                total: None
+            })]
+        );
+    }
+
+    #[test]
+    fn journal_from_cash_events() {
+        let events = vec![
+            AccountEvent::new_cash_deposited(iphone_launched_at(), "500.00 USD".to_string()),
+            AccountEvent::new_cash_withdrawn(iphone_launched_at(), "200.00 USD".to_string()),
+        ];
+        let journal = Journal::new(events);
+        assert_eq!(
+            journal.entries,
+            vec![
+                JournalEntry::Deposit(JournalRow {
+                    date: Some(iphone_launched_at().date()),
+                    rtype: JournalRowType::Deposit,
+                    identifier: StockIdentifier::from("USD"),
+                    amount: 1.0,
+                    price: Some(Amount::from("500.00 USD")),
+                    total: Some(Amount::from("500.00 USD"))
+                }),
+                JournalEntry::Withdrawal(JournalRow {
+                    date: Some(iphone_launched_at().date()),
+                    rtype: JournalRowType::Withdrawal,
+                    identifier: StockIdentifier::from("USD"),
+                    amount: 1.0,
+                    price: Some(Amount::from("200.00 USD")),
+                    total: Some(Amount::from("200.00 USD"))
+                })
+            ]
+        );
+    }
+
+    #[test]
+    fn journal_totals_include_fees() {
+        let events = vec![
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "100.00 USD".to_string(),
+                "AAPL".to_string(),
+            )
+            .with_fee("5.00 USD".to_string())
+            .unwrap(),
+            AccountEvent::new_stocks_sold(
+                iphone_launched_at(),
+                10.0,
+                "100.00 USD".to_string(),
+                "AAPL".to_string(),
+            )
+            .with_fee("5.00 USD".to_string())
+            .unwrap(),
+            AccountEvent::new_fee_charged(iphone_launched_at(), "2.00 USD".to_string()),
+        ];
+        let journal = Journal::new(events);
+        let totals = journal
+            .entries
+            .iter()
+            .map(|entry| entry.row().total.clone())
+            .collect::<Vec<Option<Amount>>>();
+
+        assert_eq!(
+            totals,
+            vec![
+                Some(Amount::from("1005.00 USD")),
+                Some(Amount::from("995.00 USD")),
+                Some(Amount::from("2.00 USD"))
+            ]
+        );
+    }
+
+    #[test]
+    fn journal_converts_totals_at_rate_on_date() {
+        let day = chrono::Duration::days(1);
+        let events = vec![
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "100.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_exchange_rate_obtained(
+                iphone_launched_at() + day,
+                "0.50 EUR".to_string(),
+                "USD".to_string(),
+            ),
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at() + day,
+                1.0,
+                "100.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+        ];
+        let journal = Journal::new(events).in_base_currency("EUR".into());
+
+        let totals = journal
+            .entries
+            .iter()
+            .map(|entry| match entry {
+                JournalEntry::Buy(row) => journal.converted_total(row),
+                _ => panic!("Unexpected journal entry"),
+            })
+            .collect::<Vec<Option<Amount>>>();
+        assert_eq!(totals, vec![None, Some(Amount::from("50.00 EUR"))]);
+    }
+
     #[test]
     fn journal_from_price_obtained_events() {
         let events = vec![
@@ -164,4 +458,52 @@ mod tests {
         let journal = Journal::new(events);
         assert_eq!(journal.entries, vec![]);
     }
+
+    #[test]
+    fn journal_shows_the_correction_trail() {
+        let day = chrono::Duration::days(1);
+        let deposit =
+            AccountEvent::new_cash_deposited(iphone_launched_at(), "100.00 USD".to_string());
+        let id = uuid::Uuid::new_v4();
+        let events = vec![
+            deposit.clone(),
+            AccountEvent::new_event_amended(
+                iphone_launched_at() + day,
+                id,
+                deposit.clone().with_price("10.00 USD".to_string()).unwrap(),
+            ),
+            AccountEvent::new_event_voided(iphone_launched_at() + day, id, deposit),
+        ];
+        let journal = Journal::new(events);
+
+        let rows = journal
+            .entries
+            .iter()
+            .map(|entry| {
+                let row = entry.row();
+                (row.date, &row.rtype, row.total.clone())
+            })
+            .collect::<Vec<_>>();
+        let corrected_at = Some((iphone_launched_at() + day).date());
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    Some(iphone_launched_at().date()),
+                    &JournalRowType::Deposit,
+                    Some(Amount::from("100.00 USD"))
+                ),
+                (
+                    corrected_at,
+                    &JournalRowType::Amend,
+                    Some(Amount::from("10.00 USD"))
+                ),
+                (
+                    corrected_at,
+                    &JournalRowType::Void,
+                    Some(Amount::from("100.00 USD"))
+                ),
+            ]
+        );
+    }
 }
@@ -0,0 +1,165 @@
+use serde::Serialize;
+
+use crate::{
+    dashboard::Dashboard,
+    exchange_rates::Consolidated,
+    journal::{Journal, JournalRow},
+    value_objects::{Amount, Amounts, Asset, Currency},
+};
+
+#[derive(Serialize)]
+struct DashboardJson<'a> {
+    number_of_positions: f64,
+    total_buying_price: &'a Amounts,
+    cash: &'a Amounts,
+    total_value: &'a Amounts,
+    total_dividend: &'a Amounts,
+    total_withheld_tax: &'a Amounts,
+    total_fees: &'a Amounts,
+    total_unrealized_profit_loss: &'a Amounts,
+    total_realized_profit_loss: &'a Amounts,
+    total_profit_loss: &'a Amounts,
+    base_currency: Option<&'a Currency>,
+    /// Only set when a base currency is set
+    total_value_in_base_currency: Option<Consolidated>,
+    total_profit_loss_in_base_currency: Option<Consolidated>,
+    assets: Vec<AssetJson>,
+}
+
+/// An asset with the figures derived from it, as shown in the portfolio table
+#[derive(Serialize)]
+struct AssetJson {
+    #[serde(flatten)]
+    asset: Asset,
+    net_dividends: Amount,
+    unrealized_profit_loss: Option<Amount>,
+    total_profit_loss: Option<Amount>,
+    value_in_base_currency: Option<Amount>,
+}
+
+#[derive(Serialize)]
+struct JournalJson<'a> {
+    base_currency: Option<&'a Currency>,
+    rows: Vec<JournalRowJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct JournalRowJson<'a> {
+    #[serde(flatten)]
+    row: &'a JournalRow,
+    total_in_base_currency: Option<Amount>,
+}
+
+/// The dashboard totals and assets as JSON, with assets sorted by ticker
+pub fn dashboard_to_json(dashboard: &Dashboard) -> serde_json::Result<String> {
+    let mut assets = dashboard.assets();
+    assets.sort_by(|a, b| a.identifier.ticker.cmp(&b.identifier.ticker));
+
+    let assets = assets
+        .into_iter()
+        .map(|asset| AssetJson {
+            net_dividends: asset.net_dividends(),
+            unrealized_profit_loss: asset.unrealized_profit_loss(),
+            total_profit_loss: asset.total_profit_loss(),
+            value_in_base_currency: asset
+                .value
+                .as_ref()
+                .and_then(|value| dashboard.convert(value)),
+            asset,
+        })
+        .collect();
+
+    let json = DashboardJson {
+        number_of_positions: dashboard.number_of_positions,
+        total_buying_price: &dashboard.total_buying_price,
+        cash: &dashboard.cash,
+        total_value: &dashboard.total_value,
+        total_dividend: &dashboard.total_dividend,
+        total_withheld_tax: &dashboard.total_withheld_tax,
+        total_fees: &dashboard.total_fees,
+        total_unrealized_profit_loss: &dashboard.total_unrealized_profit_loss,
+        total_realized_profit_loss: &dashboard.total_realized_profit_loss,
+        total_profit_loss: &dashboard.total_profit_loss,
+        base_currency: dashboard.base_currency(),
+        total_value_in_base_currency: dashboard.consolidate(&dashboard.total_value),
+        total_profit_loss_in_base_currency: dashboard.consolidate(&dashboard.total_profit_loss),
+        assets,
+    };
+    serde_json::to_string_pretty(&json)
+}
+
+/// The journal rows as JSON, in the order of the journal
+pub fn journal_to_json(journal: &Journal) -> serde_json::Result<String> {
+    let rows = journal
+        .entries
+        .iter()
+        .map(|entry| JournalRowJson {
+            row: entry.row(),
+            total_in_base_currency: journal.converted_total(entry.row()),
+        })
+        .collect();
+
+    let json = JournalJson {
+        base_currency: journal.base_currency(),
+        rows,
+    };
+    serde_json::to_string_pretty(&json)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::{date_utils::fixtures::iphone_launched_at, events::AccountEvent};
+
+    use super::*;
+
+    #[test]
+    fn test_dashboard_to_json() {
+        let events = vec![
+            AccountEvent::new_cash_deposited(iphone_launched_at(), "1000.00 USD".to_string()),
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "50.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+        ];
+        let json: Value =
+            serde_json::from_str(&dashboard_to_json(&Dashboard::new(events)).unwrap()).unwrap();
+
+        assert_eq!(json["cash"], json!([{"num": "500.00", "currency": "USD"}]));
+        assert_eq!(json["assets"][0]["identifier"], json!({"ticker": "AAPL"}));
+        assert_eq!(
+            json["assets"][0]["cost_basis"],
+            json!({"num": "500.00", "currency": "USD"})
+        );
+        assert_eq!(json["assets"][0]["unrealized_profit_loss"], Value::Null);
+        assert_eq!(json["total_value_in_base_currency"], Value::Null);
+    }
+
+    #[test]
+    fn test_journal_to_json() {
+        let events = vec![AccountEvent::new_stocks_bought(
+            iphone_launched_at(),
+            10.0,
+            "50.00 USD".to_string(),
+            "AAPL".to_string(),
+        )];
+        let json: Value =
+            serde_json::from_str(&journal_to_json(&Journal::new(events)).unwrap()).unwrap();
+
+        assert_eq!(
+            json["rows"],
+            json!([{
+                "date": "2007-01-09",
+                "rtype": "Buy",
+                "identifier": {"ticker": "AAPL"},
+                "amount": 10.0,
+                "price": {"num": "50.00", "currency": "USD"},
+                "total": {"num": "500.00", "currency": "USD"},
+                "total_in_base_currency": null
+            }])
+        );
+    }
+}
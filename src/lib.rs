@@ -1,11 +1,21 @@
+pub mod cost_basis;
 pub mod cqrs;
+pub mod envelope;
 pub mod event_store;
 pub mod events;
+pub mod exchange_rates;
+pub mod hash_chain;
+pub mod import;
+pub mod price_provider;
+pub mod snapshot;
 pub mod value_objects;
 
 pub mod date_utils;
 
 pub mod dashboard;
 pub mod journal;
+pub mod withholding;
 
 pub mod cli_output;
+pub mod csv_output;
+pub mod json_output;
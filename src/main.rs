@@ -1,41 +1,134 @@
-use std::env;
+use std::{collections::HashSet, env};
 
 use bullboard::{
+    cost_basis::CostBasisMethod,
     cqrs::CqrsFramework,
+    csv_output::{dashboard_to_csv, journal_to_csv},
     dashboard::Dashboard,
     date_utils::{now, parse_datetime_or},
-    event_store::{sqlite::SqliteEventStore, EventStore},
+    envelope::{apply_corrections, into_events, version, wrap_events, EventEnvelope, EventLog},
+    event_store::{self, query::EventQuery, EventStore, EventStoreError},
     events::AccountEvent,
+    hash_chain::verify_chain,
+    import::{Import, ImportProfile},
     journal::Journal,
+    json_output::{dashboard_to_json, journal_to_json},
+    price_provider::{fetch_prices, file::FilePriceProvider},
+    snapshot::dashboard_from_store,
+    value_objects::{Currency, StockIdentifier},
+    withholding::WithholdingReport,
 };
+use uuid::Uuid;
 
 mod cli;
 mod demo;
 
+/// The account used when none is given, which holds the events stored before accounts existed
+const DEFAULT_ACCOUNT: &str = "ber";
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let matches = cli::build_cli().get_matches();
 
-    let db_file = env::var("BULLBOARD_DB_PATH").unwrap_or("bullboard.db".to_string());
-    let cqrs = CqrsFramework::new(SqliteEventStore::new(&db_file)?);
+    let db_file = matches
+        .get_one::<String>("store")
+        .cloned()
+        .or(env::var("BULLBOARD_DB_PATH").ok())
+        .unwrap_or("bullboard.db".to_string());
+    let cqrs = CqrsFramework::new(event_store::open(&db_file)?);
+    let accounts = accounts(&matches);
 
     let output: String = match matches.subcommand() {
         Some(("demo", _)) => demo::demo().to_string(),
         Some(("add", sub_cmd)) => {
-            handle_add(sub_cmd, cqrs);
+            handle_add(sub_cmd, cqrs, single_account(&accounts)?)?;
             "".to_string() // TODO: decide what we want to show to the user.
         }
-        Some(("journal", _)) => {
-            let events = cqrs.store.get_events("ber")?;
-            Journal::new(events).to_string()
+        Some(("void", sub_cmd)) => handle_void(sub_cmd, cqrs, single_account(&accounts)?)?,
+        Some(("amend", sub_cmd)) => handle_amend(sub_cmd, cqrs, single_account(&accounts)?)?,
+        Some(("events", _)) => EventLog {
+            envelopes: account_events(&cqrs, &accounts, &EventQuery::new())?,
+        }
+        .to_string(),
+        Some(("accounts", sub_cmd)) => match sub_cmd.subcommand() {
+            Some(("list", _)) => handle_accounts_list(cqrs)?,
+            Some(("create", sub_cmd)) => handle_accounts_create(sub_cmd, cqrs)?,
+            _ => unreachable!(),
+        },
+        Some(("import", sub_cmd)) => handle_import(sub_cmd, cqrs, single_account(&accounts)?)?,
+        Some(("prices", sub_cmd)) => {
+            let account = single_account(&accounts)?;
+            match sub_cmd.subcommand() {
+                Some(("import", sub_cmd)) => handle_prices_import(sub_cmd, cqrs, account)?,
+                Some(("fetch", sub_cmd)) => handle_prices_fetch(sub_cmd, cqrs, account)?,
+                _ => unreachable!(),
+            }
+        }
+        Some(("journal", sub_cmd)) => {
+            let events = journal_events(sub_cmd, &cqrs, &accounts, base_currency(&matches))?;
+            let journal = Journal::new(into_events(events));
+            let journal = match base_currency(&matches) {
+                Some(base_currency) => journal.in_base_currency(base_currency),
+                None => journal,
+            };
+            match format(&matches) {
+                "json" => format!("\n{}\n", journal_to_json(&journal)?),
+                "csv" => journal_to_csv(&journal)?,
+                _ => journal.to_string(),
+            }
+        }
+        Some(("withholding", _)) => {
+            let events = account_events(&cqrs, &accounts, &EventQuery::new())?;
+            WithholdingReport::new(into_events(apply_corrections(events))).to_string()
         }
-        Some(("dashboard", _)) => {
-            let events = cqrs.store.get_events("ber")?;
-            Dashboard::new(events).to_string()
+        Some(("dashboard", sub_cmd)) => {
+            let cost_basis_method = sub_cmd
+                .get_one::<String>("cost-basis")
+                .cloned()
+                .or(env::var("BULLBOARD_COST_BASIS").ok())
+                .map(|method| method.parse::<CostBasisMethod>())
+                .transpose()?
+                .unwrap_or_default();
+
+            let as_of = sub_cmd.get_one::<String>("as-of");
+            let as_of = parse_datetime_or(as_of.cloned(), now).expect("Failed to parse date");
+
+            let dashboard = match accounts.as_slice() {
+                [account] => {
+                    dashboard_from_store(&cqrs.store, account, cost_basis_method, as_of.date())?
+                }
+                // Accounts are folded together, which snapshots of a single account do not cover
+                _ => {
+                    let query = EventQuery::new().until(as_of.date());
+                    let events = account_events(&cqrs, &accounts, &query)?;
+                    Dashboard::with_cost_basis_method(
+                        into_events(apply_corrections(events)),
+                        cost_basis_method,
+                    )
+                }
+            };
+            let dashboard = match base_currency(&matches) {
+                Some(base_currency) => dashboard.in_base_currency(base_currency),
+                None => dashboard,
+            };
+            match format(&matches) {
+                "json" => format!("\n{}\n", dashboard_to_json(&dashboard)?),
+                "csv" => dashboard_to_csv(&dashboard)?,
+                _ => dashboard.to_string(),
+            }
         }
         Some(("init", _)) => {
-            cqrs.store.init().unwrap();
+            cqrs.store.init()?;
             "".to_string()
         }
+        Some(("migrate", _)) => format!("\nMigrated {} events\n", cqrs.store.migrate()?),
+        Some(("verify", _)) => {
+            let (output, intact) = handle_verify(cqrs)?;
+            if !intact {
+                print!("{}", output);
+                std::process::exit(1);
+            }
+            output
+        }
         Some((&_, _)) => todo!(),
         None => unreachable!(),
     };
@@ -45,7 +138,395 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn handle_add<T>(sub_cmd: &clap::ArgMatches, cqrs: CqrsFramework<T>)
+fn base_currency(matches: &clap::ArgMatches) -> Option<Currency> {
+    matches
+        .get_one::<String>("base-currency")
+        .cloned()
+        .or(env::var("BULLBOARD_BASE_CURRENCY").ok())
+        .map(Currency::from)
+}
+
+fn format(matches: &clap::ArgMatches) -> &str {
+    matches
+        .get_one::<String>("format")
+        .map(|format| format.as_str())
+        .unwrap_or("table")
+}
+
+/// The accounts to work on. Reports combine the events of all of them.
+fn accounts(matches: &clap::ArgMatches) -> Vec<String> {
+    match matches.get_many::<String>("account") {
+        Some(accounts) => accounts.cloned().collect(),
+        None => env::var("BULLBOARD_ACCOUNT")
+            .map(|accounts| accounts.split(',').map(str::to_string).collect())
+            .unwrap_or(vec![DEFAULT_ACCOUNT.to_string()]),
+    }
+}
+
+/// The account to store events in, which can only be one
+fn single_account(accounts: &[String]) -> Result<&str, Box<dyn std::error::Error>> {
+    match accounts {
+        [account] => Ok(account),
+        _ => Err(format!(
+            "Events are stored in a single account, but got {}",
+            accounts.join(", ")
+        )
+        .into()),
+    }
+}
+
+/// The events of all accounts that match the query, merged in the order they happened
+fn account_events<T>(
+    cqrs: &CqrsFramework<T>,
+    accounts: &[String],
+    query: &EventQuery,
+) -> Result<Vec<EventEnvelope>, EventStoreError>
+where
+    T: EventStore,
+{
+    let mut envelopes = vec![];
+    for account in accounts {
+        envelopes.extend(cqrs.store.query(account, query)?);
+    }
+    // Stable, so the events of each account keep their order
+    envelopes.sort_by_key(|envelope| envelope.event.created_at());
+    Ok(envelopes)
+}
+
+/// The events to show in the journal. Converting to the base currency needs the rates
+/// up to the last event, which filtering on a ticker or start date would leave out.
+fn journal_events<T>(
+    sub_cmd: &clap::ArgMatches,
+    cqrs: &CqrsFramework<T>,
+    accounts: &[String],
+    base_currency: Option<Currency>,
+) -> Result<Vec<EventEnvelope>, EventStoreError>
+where
+    T: EventStore,
+{
+    let date = |name: &str| {
+        sub_cmd
+            .get_one::<String>(name)
+            .map(|date| parse_datetime_or(Some(date.clone()), now).expect("Failed to parse date"))
+            .map(|date_time| date_time.date())
+    };
+    let mut query = EventQuery::new();
+    let mut rates = EventQuery::new().with_event_types(&["ExchangeRateObtained"]);
+    if let Some(from) = date("from") {
+        query = query.from(from);
+    }
+    if let Some(until) = date("until") {
+        query = query.until(until);
+        rates = rates.until(until);
+    }
+    if let Some(ticker) = sub_cmd.get_one::<String>("ticker") {
+        query = query.with_identifier(StockIdentifier::from(ticker.as_str()));
+    }
+
+    let mut envelopes = account_events(cqrs, accounts, &query)?;
+    // Corrections are always read, but only those of the events shown belong in the journal
+    let shown: HashSet<Uuid> = envelopes.iter().map(|envelope| envelope.id).collect();
+    envelopes.retain(|envelope| {
+        envelope
+            .event
+            .corrected_event_id()
+            .is_none_or(|id| shown.contains(&id))
+    });
+    if base_currency.is_some() && query != EventQuery::new() {
+        let ids: HashSet<Uuid> = envelopes.iter().map(|envelope| envelope.id).collect();
+        envelopes.extend(
+            account_events(cqrs, accounts, &rates)?
+                .into_iter()
+                .filter(|envelope| !ids.contains(&envelope.id)),
+        );
+        envelopes.sort_by_key(|envelope| envelope.event.created_at());
+    }
+    Ok(envelopes)
+}
+
+fn handle_accounts_list<T>(cqrs: CqrsFramework<T>) -> Result<String, Box<dyn std::error::Error>>
+where
+    T: EventStore,
+{
+    let mut output = "\nAccounts\n".to_string();
+    for account in cqrs.store.aggregate_ids()? {
+        output.push_str(&format!("  {}\n", account));
+    }
+    Ok(output)
+}
+
+fn handle_accounts_create<T>(
+    sub_cmd: &clap::ArgMatches,
+    cqrs: CqrsFramework<T>,
+) -> Result<String, Box<dyn std::error::Error>>
+where
+    T: EventStore,
+{
+    let name = sub_cmd.get_one::<String>("NAME").unwrap();
+    if cqrs.store.aggregate_ids()?.contains(name) {
+        return Err(format!("Account {} already exists", name).into());
+    }
+
+    let envelope = EventEnvelope::new(AccountEvent::new_account_opened(now()))
+        .with_source("accounts create".to_string());
+    // Fails when the account was created meanwhile
+    cqrs.store.persist_expected(name, &[envelope], 0)?;
+    Ok(format!("\nCreated account {}\n", name))
+}
+
+/// Walk the hash chain of every account. Returns the report, and whether all chains are intact.
+fn handle_verify<T>(cqrs: CqrsFramework<T>) -> Result<(String, bool), Box<dyn std::error::Error>>
+where
+    T: EventStore,
+{
+    let mut output = "\nVerified accounts\n".to_string();
+    let mut intact = true;
+    for account in cqrs.store.aggregate_ids()? {
+        match verify_chain(cqrs.store.get_events(&account)?) {
+            Ok(head) => output.push_str(&format!(
+                "  {}: {} events, last hash {}\n",
+                account,
+                head.events,
+                head.hash.unwrap_or_default()
+            )),
+            Err(broken) => {
+                intact = false;
+                output.push_str(&format!("  {}: {}\n", account, broken));
+            }
+        }
+    }
+    Ok((output, intact))
+}
+
+fn handle_import<T>(
+    sub_cmd: &clap::ArgMatches,
+    cqrs: CqrsFramework<T>,
+    account: &str,
+) -> Result<String, Box<dyn std::error::Error>>
+where
+    T: EventStore,
+{
+    let file = sub_cmd.get_one::<String>("FILE").unwrap();
+    let profile = match sub_cmd.get_one::<String>("profile") {
+        Some(path) => std::fs::read_to_string(path)?.parse::<ImportProfile>()?,
+        None => ImportProfile::default(),
+    };
+
+    let mut import = Import::from_csv(std::fs::File::open(file)?, &profile)?;
+    let (events, version) = stored_events(&cqrs, account)?;
+    let duplicates = import.remove_duplicates(&events);
+    let skipped = format!(
+        "{} duplicates and {} unknown rows skipped",
+        duplicates, import.skipped_rows
+    );
+
+    if sub_cmd.get_flag("dry-run") {
+        return Ok(format!(
+            "{}\nWould import {} events, {}\n",
+            Journal::new(import.events.clone()),
+            import.events.len(),
+            skipped
+        ));
+    }
+
+    let source = format!("import {}", file);
+    cqrs.store.persist_expected(
+        account,
+        &wrap_events(import.events.clone(), &source),
+        version,
+    )?;
+    Ok(format!(
+        "\nImported {} events, {}\n",
+        import.events.len(),
+        skipped
+    ))
+}
+
+fn handle_prices_import<T>(
+    sub_cmd: &clap::ArgMatches,
+    cqrs: CqrsFramework<T>,
+    account: &str,
+) -> Result<String, Box<dyn std::error::Error>>
+where
+    T: EventStore,
+{
+    let file = sub_cmd.get_one::<String>("FILE").unwrap();
+    let mut import = Import::from_price_history_csv(std::fs::File::open(file)?)?;
+    let (events, version) = stored_events(&cqrs, account)?;
+    let known = import.remove_known_prices(&events);
+
+    let source = format!("prices import {}", file);
+    cqrs.store.persist_expected(
+        account,
+        &wrap_events(import.events.clone(), &source),
+        version,
+    )?;
+    Ok(format!(
+        "\nImported {} prices, {} already known skipped\n",
+        import.events.len(),
+        known
+    ))
+}
+
+fn handle_prices_fetch<T>(
+    sub_cmd: &clap::ArgMatches,
+    cqrs: CqrsFramework<T>,
+    account: &str,
+) -> Result<String, Box<dyn std::error::Error>>
+where
+    T: EventStore,
+{
+    let quotes = sub_cmd
+        .get_one::<String>("quotes")
+        .cloned()
+        .or(env::var("BULLBOARD_QUOTES_FILE").ok())
+        .ok_or("--quotes or BULLBOARD_QUOTES_FILE is required")?;
+    let provider = FilePriceProvider::open(&quotes)?;
+    let at = sub_cmd
+        .get_one::<String>("date")
+        .map(|date| parse_datetime_or(Some(date.clone()), now).expect("Failed to parse date"));
+
+    let (events, version) = stored_events(&cqrs, account)?;
+    let mut held = Dashboard::new(events.clone())
+        .assets()
+        .into_iter()
+        .filter(|asset| asset.amount > 0.0)
+        .map(|asset| asset.identifier)
+        .collect::<Vec<_>>();
+    held.sort_by(|a, b| a.ticker.cmp(&b.ticker));
+
+    let (prices, errors) = fetch_prices(&provider, &held, at);
+    let mut import = Import {
+        events: prices,
+        skipped_rows: 0,
+    };
+    let known = import.remove_known_prices(&events);
+    let source = format!("prices fetch {}", quotes);
+    cqrs.store.persist_expected(
+        account,
+        &wrap_events(import.events.clone(), &source),
+        version,
+    )?;
+
+    let mut output = format!(
+        "\nFetched {} prices, {} already known skipped\n",
+        import.events.len(),
+        known
+    );
+    for err in errors {
+        output.push_str(&format!("{}\n", err));
+    }
+    Ok(output)
+}
+
+/// All events of the account, none when nothing was stored yet,
+/// and the version of the account they were read at
+fn stored_events<T>(
+    cqrs: &CqrsFramework<T>,
+    account: &str,
+) -> Result<(Vec<AccountEvent>, u64), EventStoreError>
+where
+    T: EventStore,
+{
+    match cqrs.store.get_events(account) {
+        Ok(envelopes) => {
+            let version = version(&envelopes);
+            Ok((into_events(apply_corrections(envelopes)), version))
+        }
+        Err(EventStoreError::AggregateNotFound(_)) => Ok((vec![], 0)),
+        Err(err) => Err(err),
+    }
+}
+
+/// The event with the given id, with the corrections applied to it, and the version of
+/// the account it was read at. Voided events and the corrections themselves cannot be corrected.
+fn correctable_event<T>(
+    sub_cmd: &clap::ArgMatches,
+    cqrs: &CqrsFramework<T>,
+    account: &str,
+) -> Result<(EventEnvelope, u64), Box<dyn std::error::Error>>
+where
+    T: EventStore,
+{
+    let id = sub_cmd.get_one::<String>("ID").unwrap();
+    let id = Uuid::parse_str(id)?;
+    let envelopes = cqrs.store.get_events(account)?;
+    let version = version(&envelopes);
+    let envelope = apply_corrections(envelopes)
+        .into_iter()
+        .find(|envelope| envelope.id == id)
+        .ok_or_else(|| format!("No event with id {} that can be corrected", id))?;
+    Ok((envelope, version))
+}
+
+fn handle_void<T>(
+    sub_cmd: &clap::ArgMatches,
+    cqrs: CqrsFramework<T>,
+    account: &str,
+) -> Result<String, Box<dyn std::error::Error>>
+where
+    T: EventStore,
+{
+    let (envelope, version) = correctable_event(sub_cmd, &cqrs, account)?;
+    let date = sub_cmd.get_one::<String>("date");
+    let date_time = parse_datetime_or(date.cloned(), now).expect("Failed to parse date");
+
+    let event = AccountEvent::new_event_voided(date_time, envelope.id, envelope.event);
+    let comment = sub_cmd.get_one::<String>("comment").cloned();
+    let correction = EventEnvelope::new(event)
+        .with_source("void".to_string())
+        .with_comment(comment);
+    cqrs.store
+        .persist_expected(account, &[correction], version)?;
+
+    Ok(format!(
+        "\nVoided {} {}\n",
+        envelope.event_type, envelope.id
+    ))
+}
+
+fn handle_amend<T>(
+    sub_cmd: &clap::ArgMatches,
+    cqrs: CqrsFramework<T>,
+    account: &str,
+) -> Result<String, Box<dyn std::error::Error>>
+where
+    T: EventStore,
+{
+    let (envelope, version) = correctable_event(sub_cmd, &cqrs, account)?;
+    let date = sub_cmd.get_one::<String>("date");
+    let date_time = parse_datetime_or(date.cloned(), now).expect("Failed to parse date");
+
+    let mut amended = envelope.event;
+    if let Some(price) = sub_cmd.get_one::<String>("price") {
+        let currency = sub_cmd
+            .get_one::<String>("currency")
+            .expect("--currency is required");
+        amended = amended.with_price(format!("{} {}", price, currency))?;
+    }
+    if let Some(amount) = sub_cmd.get_one::<String>("amount") {
+        amended = amended.with_amount(amount.parse::<f64>()?)?;
+    }
+
+    let event = AccountEvent::new_event_amended(date_time, envelope.id, amended);
+    let comment = sub_cmd.get_one::<String>("comment").cloned();
+    let correction = EventEnvelope::new(event)
+        .with_source("amend".to_string())
+        .with_comment(comment);
+    cqrs.store
+        .persist_expected(account, &[correction], version)?;
+
+    Ok(format!(
+        "\nAmended {} {}\n",
+        envelope.event_type, envelope.id
+    ))
+}
+
+fn handle_add<T>(
+    sub_cmd: &clap::ArgMatches,
+    cqrs: CqrsFramework<T>,
+    account: &str,
+) -> Result<(), Box<dyn std::error::Error>>
 where
     T: EventStore,
 {
@@ -54,32 +535,83 @@ where
     let date = sub_cmd.get_one::<String>("date");
     let date_time = parse_datetime_or(date.cloned(), now).expect("Failed to parse date");
 
-    let price = sub_cmd.get_one::<String>("price").unwrap();
-    let currency = sub_cmd.get_one::<String>("currency").unwrap();
-    let identifier = sub_cmd.get_one::<String>("identifier").unwrap();
+    let identifier = || {
+        sub_cmd
+            .get_one::<String>("identifier")
+            .expect("--identifier is required")
+            .to_string()
+    };
     let amount = sub_cmd.get_one::<String>("amount").unwrap();
+    let price = || {
+        let price = sub_cmd
+            .get_one::<String>("price")
+            .expect("--price is required");
+        let currency = sub_cmd
+            .get_one::<String>("currency")
+            .expect("--currency is required");
+        format!("{} {}", price, currency)
+    };
+    let fee = sub_cmd.get_one::<String>("fee").map(|fee| {
+        let currency = sub_cmd
+            .get_one::<String>("currency")
+            .expect("--currency is required");
+        format!("{} {}", fee, currency)
+    });
 
     let event = match etype.as_str() {
         "buy" => AccountEvent::new_stocks_bought(
             date_time,
             amount.parse::<f64>().unwrap(),
-            format!("{} {}", price, currency),
-            identifier.to_string(),
+            price(),
+            identifier(),
         ),
-        "dividend" => AccountEvent::new_dividend_paid(
+        "sell" => AccountEvent::new_stocks_sold(
             date_time,
-            format!("{} {}", price, currency),
-            identifier.to_string(),
-        ),
-        "price" => AccountEvent::new_price_obtained(
-            date_time,
-            format!("{} {}", price, currency),
-            identifier.to_string(),
+            amount.parse::<f64>().unwrap(),
+            price(),
+            identifier(),
         ),
+        "dividend" => AccountEvent::new_dividend_paid(date_time, price(), identifier()),
+        "price" => AccountEvent::new_price_obtained(date_time, price(), identifier()),
+        "rate" => AccountEvent::new_exchange_rate_obtained(date_time, price(), identifier()),
+        "deposit" => AccountEvent::new_cash_deposited(date_time, price()),
+        "withdraw" => AccountEvent::new_cash_withdrawn(date_time, price()),
+        "fee" => AccountEvent::new_fee_charged(date_time, price()),
+        "split" => {
+            let ratio = sub_cmd
+                .get_one::<String>("ratio")
+                .expect("--ratio is required");
+            AccountEvent::new_stock_split(date_time, ratio.to_string(), identifier())
+        }
         _ => panic!("Unknown event type"),
     };
+    let event = match fee {
+        Some(fee) => event.with_fee(fee)?,
+        None => event,
+    };
+    let event = match sub_cmd.get_one::<String>("tax") {
+        Some(tax) => {
+            let currency = sub_cmd
+                .get_one::<String>("currency")
+                .expect("--currency is required");
+            let country = sub_cmd.get_one::<String>("country").cloned();
+            event.with_withheld_tax(format!("{} {}", tax, currency), country)?
+        }
+        None => event,
+    };
 
-    cqrs.store
-        .persist("ber", &[event])
-        .expect("Failed to persist event");
+    // Check the event against the account as it was on the date of the event
+    let (events, version) = stored_events(&cqrs, account)?;
+    let as_of = events
+        .into_iter()
+        .filter(|stored| stored.created_at() <= event.created_at());
+    Dashboard::new(as_of.collect()).check(&event)?;
+
+    let comment = sub_cmd.get_one::<String>("comment").cloned();
+    let envelope = EventEnvelope::new(event)
+        .with_source("add".to_string())
+        .with_comment(comment);
+
+    cqrs.store.persist_expected(account, &[envelope], version)?;
+    Ok(())
 }
@@ -0,0 +1,124 @@
+use std::{error::Error, fmt::Display};
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::{
+    events::AccountEvent,
+    value_objects::{Amount, StockIdentifier},
+};
+
+pub mod file;
+
+/// A source of prices for assets, e.g. a market data API
+pub trait PriceProvider {
+    /// The most recent price known, and the date it is the price of
+    fn latest_price(
+        &self,
+        identifier: &StockIdentifier,
+    ) -> Result<(NaiveDate, Amount), PriceProviderError>;
+    /// The last price known on or before the given date
+    fn price_on(
+        &self,
+        identifier: &StockIdentifier,
+        date: NaiveDate,
+    ) -> Result<Amount, PriceProviderError>;
+}
+
+/// Ask the provider for the price of every asset on the date of `at`, or for the
+/// latest price, on the date it is for, when there is no date. Returns the prices found
+/// as events, next to the errors for the prices that were not found.
+pub fn fetch_prices(
+    provider: &dyn PriceProvider,
+    identifiers: &[StockIdentifier],
+    at: Option<NaiveDateTime>,
+) -> (Vec<AccountEvent>, Vec<PriceProviderError>) {
+    let mut events = vec![];
+    let mut errors = vec![];
+
+    for identifier in identifiers {
+        let price = match at {
+            Some(at) => provider
+                .price_on(identifier, at.date())
+                .map(|price| (at, price)),
+            None => provider
+                .latest_price(identifier)
+                .map(|(date, price)| (date.and_hms_opt(0, 0, 0).unwrap(), price)),
+        };
+        match price {
+            Ok((created_at, price)) => events.push(AccountEvent::new_price_obtained(
+                created_at,
+                format!("{} {}", price.num, price.currency),
+                identifier.to_string(),
+            )),
+            Err(err) => errors.push(err),
+        }
+    }
+
+    (events, errors)
+}
+
+#[derive(Debug)]
+pub enum PriceProviderError {
+    PriceNotFound(StockIdentifier),
+    SourceError(String),
+}
+impl Error for PriceProviderError {}
+
+impl Display for PriceProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PriceProviderError::PriceNotFound(identifier) => {
+                write!(f, "No price found for {}", identifier)
+            }
+            PriceProviderError::SourceError(msg) => write!(f, "Price source error: {}", msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::date_utils::fixtures::iphone_launched_at;
+
+    use super::{file::FilePriceProvider, *};
+
+    #[test]
+    fn test_fetch_prices_on_date() {
+        let provider = FilePriceProvider::from_csv(
+            "date,ticker,close,currency\n2007-01-08,AAPL,85.4721,USD\n".as_bytes(),
+        )
+        .unwrap();
+
+        let (events, errors) = fetch_prices(
+            &provider,
+            &["AAPL".into(), "MSFT".into()],
+            Some(iphone_launched_at()),
+        );
+
+        match &events[..] {
+            [AccountEvent::PriceObtained(event)] => {
+                assert_eq!(event.created_at, iphone_launched_at());
+                assert_eq!(event.price, Amount::from("85.4721 USD"));
+            }
+            _ => panic!("Expected one price"),
+        }
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].to_string(), "No price found for MSFT");
+    }
+
+    #[test]
+    fn test_fetch_latest_prices_on_the_date_of_the_quote() {
+        let provider = FilePriceProvider::from_csv(
+            "date,ticker,close,currency\n2007-01-08,AAPL,85.4721,USD\n".as_bytes(),
+        )
+        .unwrap();
+
+        let (events, _) = fetch_prices(&provider, &["AAPL".into()], None);
+
+        match &events[..] {
+            [AccountEvent::PriceObtained(event)] => {
+                assert_eq!(event.created_at.to_string(), "2007-01-08 00:00:00");
+            }
+            _ => panic!("Expected one price"),
+        }
+    }
+}
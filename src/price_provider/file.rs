@@ -0,0 +1,151 @@
+use std::{fs::File, io::Read, path::Path};
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::value_objects::{Amount, Currency, StockIdentifier};
+
+use super::{PriceProvider, PriceProviderError};
+
+/// Quotes read from a local file, so prices can be fetched without a network
+pub struct FilePriceProvider {
+    quotes: Vec<Quote>,
+}
+
+/// One closing price, as a row in a CSV file or an object in a JSON list
+#[derive(Debug, Clone, Deserialize)]
+struct Quote {
+    date: NaiveDate,
+    ticker: String,
+    close: Decimal,
+    currency: String,
+}
+
+impl FilePriceProvider {
+    /// Read the quotes from a .json file, or else a CSV file with the columns
+    /// date, ticker, close and currency
+    pub fn open(path: &str) -> Result<Self, PriceProviderError> {
+        let file = File::open(path)?;
+        let is_json = Path::new(path)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+
+        if is_json {
+            Self::from_json(file)
+        } else {
+            Self::from_csv(file)
+        }
+    }
+
+    pub fn from_csv(reader: impl Read) -> Result<Self, PriceProviderError> {
+        let quotes = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader)
+            .deserialize()
+            .collect::<Result<Vec<Quote>, csv::Error>>()
+            .map_err(|err| PriceProviderError::SourceError(err.to_string()))?;
+        Ok(Self { quotes })
+    }
+
+    pub fn from_json(reader: impl Read) -> Result<Self, PriceProviderError> {
+        let quotes = serde_json::from_reader(reader)
+            .map_err(|err| PriceProviderError::SourceError(err.to_string()))?;
+        Ok(Self { quotes })
+    }
+}
+
+impl FilePriceProvider {
+    /// The last quote of the stock on or before the date
+    fn quote_on(
+        &self,
+        identifier: &StockIdentifier,
+        date: NaiveDate,
+    ) -> Result<&Quote, PriceProviderError> {
+        self.quotes
+            .iter()
+            .filter(|quote| quote.ticker == identifier.ticker && quote.date <= date)
+            .max_by_key(|quote| quote.date)
+            .ok_or(PriceProviderError::PriceNotFound(identifier.clone()))
+    }
+}
+
+impl Quote {
+    fn price(&self) -> Amount {
+        Amount::new(self.close, Currency::from(self.currency.as_str()))
+    }
+}
+
+impl PriceProvider for FilePriceProvider {
+    fn latest_price(
+        &self,
+        identifier: &StockIdentifier,
+    ) -> Result<(NaiveDate, Amount), PriceProviderError> {
+        self.quote_on(identifier, NaiveDate::MAX)
+            .map(|quote| (quote.date, quote.price()))
+    }
+
+    fn price_on(
+        &self,
+        identifier: &StockIdentifier,
+        date: NaiveDate,
+    ) -> Result<Amount, PriceProviderError> {
+        self.quote_on(identifier, date).map(Quote::price)
+    }
+}
+
+impl From<std::io::Error> for PriceProviderError {
+    fn from(err: std::io::Error) -> Self {
+        PriceProviderError::SourceError(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUOTES: &str = "date,ticker,close,currency
+2021-10-29,MSFT,65.00,USD
+2021-11-30,MSFT,70.00,USD
+2021-11-30,AAPL,160.00,USD
+";
+
+    #[test]
+    fn test_latest_price() {
+        let provider = FilePriceProvider::from_csv(QUOTES.as_bytes()).unwrap();
+        assert_eq!(
+            provider.latest_price(&"MSFT".into()).unwrap(),
+            (
+                NaiveDate::from_ymd_opt(2021, 11, 30).unwrap(),
+                Amount::from("70.00 USD")
+            )
+        );
+    }
+
+    #[test]
+    fn test_price_on_uses_last_price_before_date() {
+        let provider = FilePriceProvider::from_csv(QUOTES.as_bytes()).unwrap();
+        let date = NaiveDate::from_ymd_opt(2021, 11, 15).unwrap();
+
+        assert_eq!(
+            provider.price_on(&"MSFT".into(), date).unwrap(),
+            Amount::from("65.00 USD")
+        );
+        assert!(matches!(
+            provider.price_on(&"AAPL".into(), date),
+            Err(PriceProviderError::PriceNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_json() {
+        let quotes =
+            r#"[{"date": "2021-10-29", "ticker": "MSFT", "close": "65.00", "currency": "USD"}]"#;
+        let provider = FilePriceProvider::from_json(quotes.as_bytes()).unwrap();
+
+        assert_eq!(
+            provider.latest_price(&"MSFT".into()).unwrap().1,
+            Amount::from("65.00 USD")
+        );
+    }
+}
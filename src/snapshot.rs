@@ -0,0 +1,326 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cost_basis::CostBasisMethod,
+    dashboard::Dashboard,
+    envelope::{Corrections, EventEnvelope},
+    event_store::{query::EventQuery, EventStore, EventStoreError},
+    hash_chain::sha256_hex,
+};
+
+/// The folded state of a projection of an aggregate, up to and including an event,
+/// so that only the events stored after it have to be replayed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// What was folded, e.g. "dashboard fifo"
+    pub projection: String,
+    /// The version of the code that folded it. Snapshots of other versions are not used.
+    pub version: String,
+    /// The sequence of the last event folded
+    pub sequence: u64,
+    /// The date of the latest event folded. Events stored later, but dated before it,
+    /// would have been folded in a different order.
+    pub created_at: NaiveDateTime,
+    /// The serialized state
+    pub state: String,
+}
+
+/// The code that folds the events into the dashboard. Any change to it changes the version,
+/// which invalidates the snapshots made with the old code.
+const DASHBOARD_SOURCES: [&str; 5] = [
+    include_str!("dashboard.rs"),
+    include_str!("cost_basis.rs"),
+    include_str!("exchange_rates.rs"),
+    include_str!("value_objects.rs"),
+    include_str!("events.rs"),
+];
+
+pub fn dashboard_version() -> String {
+    sha256_hex(DASHBOARD_SOURCES.concat().as_bytes())
+}
+
+/// Build the dashboard of the aggregate as of the end of the given date, starting from
+/// the last snapshot when it can be used, and save a new snapshot when there were new events.
+pub fn dashboard_from_store<E>(
+    store: &E,
+    aggregate_id: &str,
+    cost_basis_method: CostBasisMethod,
+    as_of: NaiveDate,
+) -> Result<Dashboard, EventStoreError>
+where
+    E: EventStore + ?Sized,
+{
+    let projection = format!("dashboard {}", cost_basis_method);
+    let snapshot = store
+        .get_snapshot(aggregate_id, &projection)?
+        .filter(|snapshot| snapshot.version == dashboard_version())
+        .filter(|snapshot| snapshot.created_at.date() <= as_of);
+
+    if let Some(snapshot) = snapshot {
+        let envelopes = store.get_events_after(aggregate_id, snapshot.sequence)?;
+        if can_replay_after(&snapshot, &envelopes) {
+            let dashboard: Dashboard = serde_json::from_str(&snapshot.state)?;
+            let mut fold = Fold::new(dashboard, Corrections::default(), as_of);
+            for envelope in envelopes {
+                fold.handle_envelope(envelope);
+            }
+            return fold.save(store, aggregate_id, &projection);
+        }
+    }
+
+    // The corrections apply to events stored before them, so they are read up front
+    let corrections = store.query(
+        aggregate_id,
+        &EventQuery::new().with_event_types(&["EventVoided", "EventAmended"]),
+    )?;
+    let mut fold = Fold::new(
+        Dashboard::with_cost_basis_method(vec![], cost_basis_method),
+        Corrections::new(&corrections),
+        as_of,
+    );
+    store.stream_events(aggregate_id, &EventQuery::new(), &mut |envelope| {
+        fold.handle_envelope(envelope);
+        Ok(())
+    })?;
+    fold.save(store, aggregate_id, &projection)
+}
+
+/// Events stored after the snapshot can be replayed on top of it, unless they
+/// happened before the last event in it, or correct an event in it
+fn can_replay_after(snapshot: &Snapshot, envelopes: &[EventEnvelope]) -> bool {
+    envelopes.iter().all(|envelope| {
+        envelope.event.created_at() >= snapshot.created_at && !envelope.event.is_correction()
+    })
+}
+
+/// Folds the events into the dashboard one at a time, keeping track of what a snapshot
+/// of the dashboard would cover
+struct Fold {
+    dashboard: Dashboard,
+    corrections: Corrections,
+    as_of: NaiveDate,
+    /// The sequence of the last event stored
+    last: Option<u64>,
+    /// The date of the latest event
+    latest: Option<NaiveDateTime>,
+}
+
+impl Fold {
+    fn new(dashboard: Dashboard, corrections: Corrections, as_of: NaiveDate) -> Self {
+        Self {
+            dashboard,
+            corrections,
+            as_of,
+            last: None,
+            latest: None,
+        }
+    }
+
+    fn handle_envelope(&mut self, envelope: EventEnvelope) {
+        self.last = self.last.max(Some(envelope.sequence));
+        self.latest = self.latest.max(Some(envelope.event.created_at()));
+
+        if let Some(envelope) = self.corrections.apply(envelope) {
+            if envelope.event.created_at().date() <= self.as_of {
+                self.dashboard.handle_event(&envelope.event);
+            }
+        }
+    }
+
+    /// Save a snapshot when there were events, and none was left out for being
+    /// after the date
+    fn save<E>(
+        self,
+        store: &E,
+        aggregate_id: &str,
+        projection: &str,
+    ) -> Result<Dashboard, EventStoreError>
+    where
+        E: EventStore + ?Sized,
+    {
+        if let (Some(sequence), Some(created_at)) = (self.last, self.latest) {
+            if created_at.date() <= self.as_of {
+                let snapshot = Snapshot {
+                    projection: projection.to_string(),
+                    version: dashboard_version(),
+                    sequence,
+                    created_at,
+                    state: serde_json::to_string(&self.dashboard)?,
+                };
+                store.save_snapshot(aggregate_id, &snapshot)?;
+            }
+        }
+        Ok(self.dashboard)
+    }
+}
+
+/// Serialize a map as a list of key and value pairs, for keys that JSON cannot use,
+/// like structs and tuples
+pub(crate) mod map_as_pairs {
+    use std::{collections::HashMap, hash::Hash};
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Vec::<(K, V)>::deserialize(deserializer).map(|pairs| pairs.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::{
+        date_utils::fixtures::iphone_launched_at, event_store::memory::MemoryEventStore,
+        events::AccountEvent, value_objects::Amount,
+    };
+
+    use super::*;
+
+    fn deposit(days: i64, amount: &str) -> EventEnvelope {
+        EventEnvelope::new(AccountEvent::new_cash_deposited(
+            iphone_launched_at() + Duration::days(days),
+            amount.to_string(),
+        ))
+    }
+
+    fn today() -> NaiveDate {
+        crate::date_utils::now().date()
+    }
+
+    fn cash(dashboard: &Dashboard) -> Vec<Amount> {
+        dashboard.cash.sorted()
+    }
+
+    fn snapshot_of(store: &MemoryEventStore) -> Option<Snapshot> {
+        store.get_snapshot("ber", "dashboard fifo").unwrap()
+    }
+
+    /// A snapshot with a made up balance, to tell if it was used
+    fn save_fake_snapshot(store: &MemoryEventStore, version: String) {
+        let snapshot = snapshot_of(store).unwrap();
+        let dashboard = Dashboard::new(vec![AccountEvent::new_cash_deposited(
+            iphone_launched_at(),
+            "42.00 USD".to_string(),
+        )]);
+        let fake = Snapshot {
+            version,
+            state: serde_json::to_string(&dashboard).unwrap(),
+            ..snapshot
+        };
+        store.save_snapshot("ber", &fake).unwrap();
+    }
+
+    #[test]
+    fn test_saves_a_snapshot_at_the_last_event() {
+        let store = MemoryEventStore::default();
+        store
+            .persist("ber", &[deposit(0, "1.00 USD"), deposit(1, "2.00 USD")])
+            .unwrap();
+
+        let dashboard =
+            dashboard_from_store(&store, "ber", CostBasisMethod::Fifo, today()).unwrap();
+
+        let snapshot = snapshot_of(&store).unwrap();
+        assert_eq!(snapshot.sequence, 2);
+        assert_eq!(
+            snapshot.created_at,
+            iphone_launched_at() + Duration::days(1)
+        );
+        assert_eq!(snapshot.version, dashboard_version());
+        assert_eq!(cash(&dashboard), vec![Amount::from("3.00 USD")]);
+    }
+
+    #[test]
+    fn test_replays_the_events_after_the_snapshot() {
+        let store = MemoryEventStore::default();
+        store.persist("ber", &[deposit(0, "1.00 USD")]).unwrap();
+        dashboard_from_store(&store, "ber", CostBasisMethod::Fifo, today()).unwrap();
+        save_fake_snapshot(&store, dashboard_version());
+        store.persist("ber", &[deposit(1, "2.00 USD")]).unwrap();
+
+        let dashboard =
+            dashboard_from_store(&store, "ber", CostBasisMethod::Fifo, today()).unwrap();
+
+        assert_eq!(cash(&dashboard), vec![Amount::from("44.00 USD")]);
+        assert_eq!(snapshot_of(&store).unwrap().sequence, 2);
+    }
+
+    #[test]
+    fn test_ignores_snapshots_of_another_version() {
+        let store = MemoryEventStore::default();
+        store.persist("ber", &[deposit(0, "1.00 USD")]).unwrap();
+        dashboard_from_store(&store, "ber", CostBasisMethod::Fifo, today()).unwrap();
+        save_fake_snapshot(&store, "old".to_string());
+
+        let dashboard =
+            dashboard_from_store(&store, "ber", CostBasisMethod::Fifo, today()).unwrap();
+
+        assert_eq!(cash(&dashboard), vec![Amount::from("1.00 USD")]);
+        assert_eq!(snapshot_of(&store).unwrap().version, dashboard_version());
+    }
+
+    #[test]
+    fn test_rebuilds_when_an_event_is_backdated() {
+        let store = MemoryEventStore::default();
+        store.persist("ber", &[deposit(1, "1.00 USD")]).unwrap();
+        dashboard_from_store(&store, "ber", CostBasisMethod::Fifo, today()).unwrap();
+        save_fake_snapshot(&store, dashboard_version());
+        store.persist("ber", &[deposit(0, "2.00 USD")]).unwrap();
+
+        let dashboard =
+            dashboard_from_store(&store, "ber", CostBasisMethod::Fifo, today()).unwrap();
+
+        assert_eq!(cash(&dashboard), vec![Amount::from("3.00 USD")]);
+    }
+
+    #[test]
+    fn test_rebuilds_when_an_event_is_corrected() {
+        let store = MemoryEventStore::default();
+        let first = deposit(0, "1.00 USD");
+        store
+            .persist("ber", &[first.clone(), deposit(1, "2.00 USD")])
+            .unwrap();
+        dashboard_from_store(&store, "ber", CostBasisMethod::Fifo, today()).unwrap();
+        let void = EventEnvelope::new(AccountEvent::new_event_voided(
+            iphone_launched_at() + Duration::days(2),
+            first.id,
+            first.event,
+        ));
+        store.persist("ber", &[void]).unwrap();
+
+        let dashboard =
+            dashboard_from_store(&store, "ber", CostBasisMethod::Fifo, today()).unwrap();
+
+        assert_eq!(cash(&dashboard), vec![Amount::from("2.00 USD")]);
+    }
+
+    #[test]
+    fn test_does_not_snapshot_a_partial_dashboard() {
+        let store = MemoryEventStore::default();
+        store
+            .persist("ber", &[deposit(0, "1.00 USD"), deposit(10, "2.00 USD")])
+            .unwrap();
+
+        let as_of = (iphone_launched_at() + Duration::days(1)).date();
+        let dashboard = dashboard_from_store(&store, "ber", CostBasisMethod::Fifo, as_of).unwrap();
+
+        assert_eq!(cash(&dashboard), vec![Amount::from("1.00 USD")]);
+        assert_eq!(snapshot_of(&store), None);
+    }
+}
@@ -3,11 +3,11 @@ use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     fmt::Display,
-    ops::{Add, AddAssign, Mul},
+    ops::{Add, AddAssign, Mul, Neg, Sub},
 };
 
 /// A financial asset (stock, ETF, etc.) held by the user
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Asset {
     /// The identifier of the asset
     pub identifier: StockIdentifier,
@@ -15,12 +15,27 @@ pub struct Asset {
     /// The amount of the asset held
     pub amount: f64,
 
-    /// Total amount of dividends paid for the asset
+    /// Total amount of dividends paid for the asset, before tax
     pub dividends: Amount,
 
+    /// Total amount of tax withheld on the dividends
+    pub withheld_tax: Amount,
+
+    /// Profit or loss realized by selling (part of) the asset
+    pub realized_profit_loss: Amount,
+
+    /// The price paid for the amount of the asset held
+    pub cost_basis: Amount,
+
     /// The total value of the asset based on last price obtained
     /// None means that the price has not been obtained yet.
     pub value: Option<Amount>,
+
+    /// The last price obtained for the asset
+    pub last_price: Option<Amount>,
+
+    /// The price obtained before the last price
+    pub previous_price: Option<Amount>,
 }
 
 impl Asset {
@@ -29,7 +44,52 @@ impl Asset {
             identifier: identifier.clone(),
             amount: 0.0,
             dividends: Amount::zero(Currency::default()),
+            withheld_tax: Amount::zero(Currency::default()),
+            realized_profit_loss: Amount::zero(Currency::default()),
+            cost_basis: Amount::zero(Currency::default()),
             value: None,
+            last_price: None,
+            previous_price: None,
+        }
+    }
+
+    /// The difference between the value and the cost basis of the amount held.
+    /// None means that the price has not been obtained yet, or is in another currency
+    /// than the one the asset was bought in.
+    pub fn unrealized_profit_loss(&self) -> Option<Amount> {
+        self.value
+            .clone()
+            .filter(|value| value.currency == self.cost_basis.currency)
+            .map(|value| value - self.cost_basis.clone())
+    }
+
+    /// The dividends received, after the tax withheld
+    pub fn net_dividends(&self) -> Amount {
+        self.dividends.clone() - self.withheld_tax.clone()
+    }
+
+    /// Unrealized and realized profit or loss, plus the net dividends received.
+    /// None means that the unrealized profit or loss is not known, or that the amounts
+    /// are in different currencies.
+    pub fn total_profit_loss(&self) -> Option<Amount> {
+        let unrealized = self.unrealized_profit_loss()?;
+        let net_dividends = self.net_dividends();
+        if self.realized_profit_loss.currency != unrealized.currency
+            || net_dividends.currency != unrealized.currency
+        {
+            return None;
+        }
+
+        Some(unrealized + self.realized_profit_loss.clone() + net_dividends)
+    }
+
+    /// The change of the last price since the previous price, in percent
+    pub fn price_change_percentage(&self) -> Option<Decimal> {
+        match (&self.last_price, &self.previous_price) {
+            (Some(last), Some(previous)) if !previous.num.is_zero() => {
+                Some((last.num - previous.num) / previous.num * Decimal::ONE_HUNDRED)
+            }
+            _ => None,
         }
     }
 }
@@ -58,13 +118,6 @@ impl Amounts {
         }
     }
 
-    pub(crate) fn for_currency(&self, currency: &Currency) -> Amount {
-        self.amounts
-            .get(currency)
-            .unwrap_or(&Amount::zero(currency.clone()))
-            .to_owned()
-    }
-
     pub(crate) fn upsert(&mut self, amount: Amount) {
         if let Some(existing_amount) = self.amounts.get_mut(&amount.currency) {
             existing_amount.num += amount.num;
@@ -84,6 +137,25 @@ impl Amounts {
     }
 }
 
+/// Serialized as the sorted list of amounts, so the output is stable
+impl Serialize for Amounts {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.sorted().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Amounts {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Vec::<Amount>::deserialize(deserializer).map(Amounts::new)
+    }
+}
+
 /// A number of units of certain commodity
 #[derive(Default, Debug, Clone, PartialEq, Ord, PartialOrd, Eq, Serialize, Deserialize)]
 pub struct Amount {
@@ -188,6 +260,32 @@ where
     }
 }
 
+impl Sub for Amount {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        if self.currency != rhs.currency {
+            panic!("Cannot subtract amounts of different currencies");
+        }
+
+        Self {
+            num: self.num - rhs.num,
+            currency: self.currency,
+        }
+    }
+}
+
+impl Neg for Amount {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self {
+            num: -self.num,
+            currency: self.currency,
+        }
+    }
+}
+
 impl AddAssign for Amount {
     fn add_assign(&mut self, rhs: Self) {
         if self.currency != rhs.currency {
@@ -256,6 +354,67 @@ impl Display for StockIdentifier {
     }
 }
 
+/// The ratio of a stock split: every `denominator` stocks become `numerator` stocks.
+/// A reverse split has a numerator smaller than its denominator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SplitRatio {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl SplitRatio {
+    /// The number of new stocks per old stock
+    pub fn factor(&self) -> f64 {
+        f64::from(self.numerator) / f64::from(self.denominator)
+    }
+
+    /// Adjust a price per old stock into the price per new stock
+    pub fn adjust_price(&self, price: &Amount) -> Amount {
+        Amount::new(
+            price.num * Decimal::from(self.denominator) / Decimal::from(self.numerator),
+            price.currency.clone(),
+        )
+    }
+}
+
+impl Display for SplitRatio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.numerator, self.denominator)
+    }
+}
+
+impl From<String> for SplitRatio {
+    fn from(s: String) -> Self {
+        let parts: Vec<&str> = s.split(':').collect();
+        if parts.len() != 2 {
+            panic!("Split ratio must be in the form of '4:1' - new stocks:old stocks");
+        }
+
+        let numerator = parts[0]
+            .trim()
+            .parse::<u32>()
+            .expect("Split ratio numerator is not a valid number");
+        let denominator = parts[1]
+            .trim()
+            .parse::<u32>()
+            .expect("Split ratio denominator is not a valid number");
+        if numerator == 0 || denominator == 0 {
+            panic!("Split ratio cannot contain zero");
+        }
+
+        Self {
+            numerator,
+            denominator,
+        }
+    }
+}
+
+impl From<&str> for SplitRatio {
+    fn from(s: &str) -> Self {
+        Self::from(s.to_string())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -315,6 +474,27 @@ mod tests {
         assert_eq!(amount.currency.0, "EUR".to_string());
     }
 
+    #[test]
+    fn test_amount_sub() {
+        let amount = Amount::from("123.45 EUR".to_string());
+        let amount = amount - Amount::from("100.00 EUR".to_string());
+        assert_eq!(amount.num, Decimal::from_str_exact("23.45").unwrap());
+        assert_eq!(amount.currency.0, "EUR".to_string());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_amount_sub_different_currencies() {
+        let _ = Amount::from("123.45 EUR".to_string()) - Amount::from("123.45 USD".to_string());
+    }
+
+    #[test]
+    fn test_amount_neg() {
+        let amount = -Amount::from("123.45 EUR".to_string());
+        assert_eq!(amount.num, Decimal::from_str_exact("-123.45").unwrap());
+        assert_eq!(amount.currency.0, "EUR".to_string());
+    }
+
     #[test]
     fn test_amount_add_assign() {
         let mut amount = Amount::from("123.45 EUR".to_string());
@@ -336,6 +516,66 @@ mod tests {
         assert_eq!(stock.to_string(), "AAPL");
     }
 
+    #[test]
+    fn test_asset_profit_loss() {
+        let asset = Asset {
+            amount: 10.0,
+            dividends: Amount::from("5.00 USD"),
+            realized_profit_loss: Amount::from("-2.00 USD"),
+            cost_basis: Amount::from("100.00 USD"),
+            value: Some(Amount::from("120.00 USD")),
+            ..Asset::zero(&StockIdentifier::from("AAPL"))
+        };
+
+        assert_eq!(
+            asset.unrealized_profit_loss(),
+            Some(Amount::from("20.00 USD"))
+        );
+        assert_eq!(asset.total_profit_loss(), Some(Amount::from("23.00 USD")));
+    }
+
+    #[test]
+    fn test_asset_profit_loss_without_price() {
+        let asset = Asset::zero(&StockIdentifier::from("AAPL"));
+        assert_eq!(asset.unrealized_profit_loss(), None);
+        assert_eq!(asset.total_profit_loss(), None);
+    }
+
+    #[test]
+    fn test_asset_profit_loss_with_price_in_another_currency() {
+        let asset = Asset {
+            amount: 10.0,
+            dividends: Amount::from("0.00 EUR"),
+            withheld_tax: Amount::from("0.00 EUR"),
+            realized_profit_loss: Amount::from("0.00 EUR"),
+            cost_basis: Amount::from("100.00 EUR"),
+            value: Some(Amount::from("120.00 USD")),
+            ..Asset::zero(&StockIdentifier::from("AAPL"))
+        };
+
+        assert_eq!(asset.unrealized_profit_loss(), None);
+        assert_eq!(asset.total_profit_loss(), None);
+    }
+
+    #[test]
+    fn test_asset_price_change_percentage() {
+        let asset = Asset {
+            last_price: Some(Amount::from("11.00 USD")),
+            previous_price: Some(Amount::from("10.00 USD")),
+            ..Asset::zero(&StockIdentifier::from("AAPL"))
+        };
+        assert_eq!(
+            asset.price_change_percentage(),
+            Some(Decimal::from_str_exact("10").unwrap())
+        );
+
+        let asset = Asset {
+            last_price: Some(Amount::from("11.00 USD")),
+            ..Asset::zero(&StockIdentifier::from("AAPL"))
+        };
+        assert_eq!(asset.price_change_percentage(), None);
+    }
+
     #[test]
     fn test_amounts_upsert_with_new_currency() {
         let mut amounts = Amounts::default();
@@ -369,6 +609,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_split_ratio_from_string() {
+        let ratio = SplitRatio::from("4:1");
+        assert_eq!(
+            ratio,
+            SplitRatio {
+                numerator: 4,
+                denominator: 1
+            }
+        );
+        assert_eq!(ratio.factor(), 4.0);
+        assert_eq!(ratio.to_string(), "4:1");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_split_ratio_from_invalid_string() {
+        let _ = SplitRatio::from("4");
+    }
+
+    #[test]
+    fn test_split_ratio_adjust_price() {
+        assert_eq!(
+            SplitRatio::from("4:1").adjust_price(&Amount::from("100.00 USD")),
+            Amount::from("25.00 USD")
+        );
+        assert_eq!(
+            SplitRatio::from("1:10").adjust_price(&Amount::from("2.50 USD")),
+            Amount::from("25.00 USD")
+        );
+    }
+
     #[test]
     fn test_currency_has_default() {
         let currency = Currency::default();
@@ -0,0 +1,179 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::Datelike;
+
+use crate::{
+    events::AccountEvent,
+    value_objects::{Amount, Currency, StockIdentifier},
+};
+
+/// The dividends received and the tax withheld on them, per country and year,
+/// so that we know what to reclaim
+pub struct WithholdingReport {
+    pub rows: Vec<WithholdingRow>,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct WithholdingRow {
+    /// The country that withheld the tax. None when it was not recorded
+    pub country: Option<String>,
+    pub year: i32,
+    /// The dividends paid, before tax
+    pub gross: Amount,
+    pub withheld_tax: Amount,
+}
+
+impl WithholdingRow {
+    /// The dividends received, after tax
+    pub fn net(&self) -> Amount {
+        self.gross.clone() - self.withheld_tax.clone()
+    }
+}
+
+impl WithholdingReport {
+    pub fn new(events: Vec<AccountEvent>) -> Self {
+        // The dividend is paid per stock on hand, so keep track of the amounts held
+        let mut amounts: HashMap<StockIdentifier, f64> = HashMap::new();
+        let mut rows: BTreeMap<(Option<String>, i32, Currency), WithholdingRow> = BTreeMap::new();
+
+        for event in &events {
+            match event {
+                AccountEvent::StocksBought(props) => {
+                    *amounts.entry(props.identifier.clone()).or_default() += props.amount;
+                }
+                AccountEvent::StocksSold(props) => {
+                    *amounts.entry(props.identifier.clone()).or_default() -= props.amount;
+                }
+                AccountEvent::StockSplit(props) => {
+                    if let Some(amount) = amounts.get_mut(&props.identifier) {
+                        *amount *= props.ratio.factor();
+                    }
+                }
+                AccountEvent::DividendPaid(props) => {
+                    let amount = amounts.get(&props.identifier).copied().unwrap_or(0.0);
+                    let currency = props.price.currency.clone();
+                    let key = (
+                        props.country.clone(),
+                        props.created_at.year(),
+                        currency.clone(),
+                    );
+                    let row = rows.entry(key).or_insert_with(|| WithholdingRow {
+                        country: props.country.clone(),
+                        year: props.created_at.year(),
+                        gross: Amount::zero(currency.clone()),
+                        withheld_tax: Amount::zero(currency),
+                    });
+                    row.gross += props.price.clone() * amount;
+                    row.withheld_tax += props.withheld_tax() * amount;
+                }
+                _ => {}
+            }
+        }
+
+        Self {
+            rows: rows.into_values().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::date_utils::fixtures::iphone_launched_at;
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn report_groups_withholding_by_country_and_year() {
+        let year = chrono::Duration::days(365);
+        let events = vec![
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "100.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                5.0,
+                "20.00 EUR".to_string(),
+                "ASML".to_string(),
+            ),
+            AccountEvent::new_dividend_paid(
+                iphone_launched_at(),
+                "1.00 USD".to_string(),
+                "AAPL".to_string(),
+            )
+            .with_withheld_tax("0.15 USD".to_string(), Some("US".to_string()))
+            .unwrap(),
+            AccountEvent::new_dividend_paid(
+                iphone_launched_at() + chrono::Duration::days(1),
+                "1.00 USD".to_string(),
+                "AAPL".to_string(),
+            )
+            .with_withheld_tax("0.15 USD".to_string(), Some("US".to_string()))
+            .unwrap(),
+            AccountEvent::new_dividend_paid(
+                iphone_launched_at() + year,
+                "2.00 EUR".to_string(),
+                "ASML".to_string(),
+            )
+            .with_withheld_tax("0.30 EUR".to_string(), Some("NL".to_string()))
+            .unwrap(),
+        ];
+        let report = WithholdingReport::new(events);
+
+        assert_eq!(
+            report.rows,
+            vec![
+                WithholdingRow {
+                    country: Some("NL".to_string()),
+                    year: 2008,
+                    gross: Amount::from("10.00 EUR"),
+                    withheld_tax: Amount::from("1.50 EUR"),
+                },
+                WithholdingRow {
+                    country: Some("US".to_string()),
+                    year: 2007,
+                    gross: Amount::from("20.00 USD"),
+                    withheld_tax: Amount::from("3.00 USD"),
+                },
+            ]
+        );
+        assert_eq!(report.rows[1].net(), Amount::from("17.00 USD"));
+    }
+
+    #[test]
+    fn report_counts_only_stocks_held() {
+        let events = vec![
+            AccountEvent::new_stocks_bought(
+                iphone_launched_at(),
+                10.0,
+                "100.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_stocks_sold(
+                iphone_launched_at(),
+                4.0,
+                "100.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+            AccountEvent::new_dividend_paid(
+                iphone_launched_at(),
+                "1.00 USD".to_string(),
+                "AAPL".to_string(),
+            ),
+        ];
+        let report = WithholdingReport::new(events);
+
+        assert_eq!(
+            report.rows,
+            vec![WithholdingRow {
+                country: None,
+                year: 2007,
+                gross: Amount::from("6.00 USD"),
+                withheld_tax: Amount::from("0.00 USD"),
+            }]
+        );
+    }
+}
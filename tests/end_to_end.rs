@@ -1,4 +1,7 @@
-use std::{env, path, process::Command};
+use std::{
+    env, fs, path,
+    process::{Command, Output},
+};
 
 use bullboard::value_objects::Amount;
 use cucumber::{gherkin::Step, given, then, when, World};
@@ -8,6 +11,7 @@ use tempfile::TempDir;
 #[derive(Debug, Default, World)]
 pub struct BullboardWorld {
     last_command_output: String,
+    last_command_error: String,
 
     bin: path::PathBuf,
 
@@ -17,12 +21,37 @@ pub struct BullboardWorld {
 
 impl BullboardWorld {
     fn run_command(&mut self, args: &str) {
+        let output = self.output_of(args);
+
+        if !output.status.success() {
+            panic!(
+                "Command {} {} failed with status code {}.\nOutput: {}",
+                self.bin.to_string_lossy(),
+                args,
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        self.last_command_output = String::from_utf8_lossy(&output.stdout).to_string();
+    }
+
+    /// Run a command that is expected to fail, keeping the error it reports
+    fn run_failing_command(&mut self, args: &str) {
+        let output = self.output_of(args);
+
+        if output.status.success() {
+            panic!("Command {} succeeded, but was expected to fail", args);
+        }
+        self.last_command_error = String::from_utf8_lossy(&output.stderr).to_string();
+    }
+
+    fn output_of(&mut self, args: &str) -> Output {
         let args: Vec<String> = args.split_whitespace().map(|s| s.to_string()).collect();
 
         env::set_var("BULLBOARD_DB_PATH", &self.db_path);
         self.bin = path::PathBuf::from(env!("CARGO_BIN_EXE_bullboard"));
 
-        let output = Command::new(&self.bin)
+        Command::new(&self.bin)
             .args(&args)
             .output()
             .unwrap_or_else(|_| {
@@ -31,20 +60,32 @@ impl BullboardWorld {
                     &self.bin.to_string_lossy(),
                     args.join(" ")
                 )
-            });
+            })
+    }
+}
 
-        if !output.status.success() {
-            panic!(
-                "Command {} {} failed with status code {}.\nOutput: {}",
-                self.bin.to_string_lossy(),
-                args.join(" "),
-                output.status,
-                String::from_utf8_lossy(&output.stderr)
-            );
-        }
-        self.last_command_output = String::from_utf8_lossy(&output.stdout).to_string();
+impl BullboardWorld {
+    /// Write the docstring of the step into a CSV file next to the database
+    fn write_import_file(&self, step: &Step) -> String {
+        let content = step.docstring().expect("CSV is required");
+        let dir = self.db_dir.as_ref().expect("a database file is required");
+        let file = dir.path().join("import.csv");
+        fs::write(&file, content.trim_start()).expect("Failed to write CSV");
+        file.to_string_lossy().to_string()
+    }
+
+    /// The id of the event stored at the given position, starting at 1
+    fn event_id(&self, sequence: u64) -> String {
+        let db = rusqlite::Connection::open(&self.db_path).expect("Failed to open database");
+        db.query_row(
+            "SELECT uuid FROM events WHERE sequence = ?",
+            [sequence],
+            |row| row.get(0),
+        )
+        .expect("Failed to find event")
     }
 }
+
 #[given("a database file to store events")]
 fn a_database_file_to_store_events(world: &mut BullboardWorld) {
     let temp_dir = tempfile::tempdir().expect("Failed to create tmp directory");
@@ -56,6 +97,23 @@ fn a_database_file_to_store_events(world: &mut BullboardWorld) {
     world.run_command("init");
 }
 
+#[given("a JSON Lines file to store events")]
+fn a_json_lines_file_to_store_events(world: &mut BullboardWorld) {
+    let temp_dir = tempfile::tempdir().expect("Failed to create tmp directory");
+    let db_path = temp_dir.path().join("events.jsonl");
+    let db_path_str = db_path.to_str().expect("Failed to convert path to string");
+
+    world.db_path = db_path_str.to_string();
+    world.db_dir = Some(temp_dir);
+    world.run_command("init");
+}
+
+#[given("writing the last event was cut off")]
+fn writing_the_last_event_was_cut_off(world: &mut BullboardWorld) {
+    let contents = fs::read_to_string(&world.db_path).expect("Failed to read the events");
+    fs::write(&world.db_path, &contents[..contents.len() - 20]).expect("Failed to cut off");
+}
+
 #[given("I have the following stock transactions")]
 fn i_have_the_following_stock_stransactions(world: &mut BullboardWorld, step: &Step) {
     if let Some(table) = step.table() {
@@ -79,6 +137,64 @@ fn i_check_my_dashboard(world: &mut BullboardWorld) {
     world.run_command("dashboard");
 }
 
+#[when(expr = "I check my dashboard for {string}")]
+fn i_check_my_dashboard_for(world: &mut BullboardWorld, accounts: String) {
+    world.run_command(&format!("dashboard --account {}", accounts));
+}
+
+#[when(expr = "I check my dashboard using the {string} cost basis method")]
+fn i_check_my_dashboard_using_cost_basis_method(world: &mut BullboardWorld, method: String) {
+    world.run_command(&format!("dashboard --cost-basis {}", method));
+}
+
+#[when(expr = "I check my dashboard as of {string}")]
+fn i_check_my_dashboard_as_of(world: &mut BullboardWorld, date: String) {
+    world.run_command(&format!("dashboard --as-of {}", date));
+}
+
+#[when(expr = "I check my dashboard in {string}")]
+fn i_check_my_dashboard_in(world: &mut BullboardWorld, base_currency: String) {
+    world.run_command(&format!("dashboard --base-currency {}", base_currency));
+}
+
+#[when(expr = "I check my journal as {string}")]
+fn i_check_my_journal_as(world: &mut BullboardWorld, format: String) {
+    world.run_command(&format!("journal --format {}", format.to_lowercase()));
+}
+
+#[when(expr = "I check the history of {string} in {string}")]
+fn i_check_the_history_of_in(world: &mut BullboardWorld, ticker: String, base_currency: String) {
+    world.run_command(&format!(
+        "journal --ticker {} --base-currency {}",
+        ticker, base_currency
+    ));
+}
+
+#[when(expr = "I check my journal from {string} until {string}")]
+fn i_check_my_journal_from_until(world: &mut BullboardWorld, from: String, until: String) {
+    world.run_command(&format!("journal --from {} --until {}", from, until));
+}
+
+#[when(expr = "I check my journal in {string}")]
+fn i_check_my_journal_in(world: &mut BullboardWorld, base_currency: String) {
+    world.run_command(&format!("journal --base-currency {}", base_currency));
+}
+
+#[when(expr = "the exchange rate of {string} is {string} on {string}")]
+fn the_exchange_rate_of_is_on(
+    world: &mut BullboardWorld,
+    currency: String,
+    rate: String,
+    date: String,
+) {
+    let amount: Amount = rate.into();
+
+    world.run_command(&format!(
+        "add --type rate --price {} --currency {} --identifier {} --date {}",
+        amount.num, amount.currency, currency, date
+    ));
+}
+
 #[when("I check my journal")]
 fn i_check_my_journal(world: &mut BullboardWorld) {
     world.run_command("journal");
@@ -121,6 +237,101 @@ fn pays_dividend_per_share_on(
     ));
 }
 
+#[when(
+    expr = "{string} pays {string} dividend per share with {string} tax withheld in {string} on {string}"
+)]
+fn pays_dividend_per_share_with_tax_withheld_on(
+    world: &mut BullboardWorld,
+    ticker: String,
+    dividend: String,
+    tax: String,
+    country: String,
+    date: String,
+) {
+    let amount: Amount = dividend.into();
+    let tax: Amount = tax.into();
+
+    world.run_command(&format!(
+        "add --type dividend --price {} --tax {} --country {} --currency {} --identifier {} --date {}",
+        amount.num, tax.num, country, amount.currency, ticker, date
+    ));
+}
+
+#[when("I check my withholding report")]
+fn i_check_my_withholding_report(world: &mut BullboardWorld) {
+    world.run_command("withholding");
+}
+
+#[given(expr = "I create the account {string}")]
+fn i_create_the_account(world: &mut BullboardWorld, account: String) {
+    world.run_command(&format!("accounts create {}", account));
+}
+
+#[when("I list my accounts")]
+fn i_list_my_accounts(world: &mut BullboardWorld) {
+    world.run_command("accounts list");
+}
+
+#[given(expr = "I have the following stock transactions in {string}")]
+fn i_have_the_following_stock_transactions_in(
+    world: &mut BullboardWorld,
+    step: &Step,
+    account: String,
+) {
+    if let Some(table) = step.table() {
+        for row in table.rows.iter().skip(1) {
+            let ticker: String = row[0].to_string();
+            let currency: String = row[1].parse().unwrap();
+            let amount: f64 = row[2].parse().unwrap();
+            let price: f64 = row[3].parse().unwrap();
+            let date: String = row[4].to_string();
+
+            world.run_command(&format!(
+                "add --account {} --type buy --amount {} --price {} --currency {} --identifier {} --date {}",
+                account, amount, price, currency, ticker, date
+            ));
+        }
+    }
+}
+
+#[given(expr = "I deposit {string} on {string} in {string}")]
+fn i_deposit_on_in(world: &mut BullboardWorld, amount: String, date: String, account: String) {
+    let amount: Amount = amount.into();
+
+    world.run_command(&format!(
+        "add --account {} --type deposit --price {} --currency {} --date {}",
+        account, amount.num, amount.currency, date
+    ));
+}
+
+#[given(expr = "I deposit {string} on {string}")]
+fn i_deposit_on(world: &mut BullboardWorld, amount: String, date: String) {
+    let amount: Amount = amount.into();
+
+    world.run_command(&format!(
+        "add --type deposit --price {} --currency {} --date {}",
+        amount.num, amount.currency, date
+    ));
+}
+
+#[when(expr = "I withdraw {string} on {string}")]
+fn i_withdraw_on(world: &mut BullboardWorld, amount: String, date: String) {
+    let amount: Amount = amount.into();
+
+    world.run_command(&format!(
+        "add --type withdraw --price {} --currency {} --date {}",
+        amount.num, amount.currency, date
+    ));
+}
+
+#[when(expr = "{string} splits {string} on {string}")]
+fn splits_on(world: &mut BullboardWorld, ticker: String, ratio: String, date: String) {
+    world.run_command(&format!(
+        "add --type split --ratio {} --identifier {} --date {}",
+        ratio, ticker, date
+    ));
+}
+
 #[when("I have the following stock transactions")]
 fn i_have_the_following_stock_transactions(world: &mut BullboardWorld, step: &Step) {
     if let Some(table) = step.table() {
@@ -139,6 +350,142 @@ fn i_have_the_following_stock_transactions(world: &mut BullboardWorld, step: &St
     }
 }
 
+#[when("I sell the following stocks")]
+fn i_sell_the_following_stocks(world: &mut BullboardWorld, step: &Step) {
+    if let Some(table) = step.table() {
+        for row in table.rows.iter().skip(1) {
+            let ticker: String = row[0].parse().unwrap();
+            let currency: String = row[1].parse().unwrap();
+            let amount: f64 = row[2].parse().unwrap();
+            let price: f64 = row[3].parse().unwrap();
+            let date: String = row[4].parse().unwrap();
+
+            world.run_command(&format!(
+                "add --type sell --amount {} --price {} --currency {} --identifier {} --date {}",
+                amount, price, currency, ticker, date
+            ));
+        }
+    }
+}
+
+#[when(expr = "I try to sell {float} {string} at {string} on {string}")]
+fn i_try_to_sell(
+    world: &mut BullboardWorld,
+    amount: f64,
+    ticker: String,
+    price: String,
+    date: String,
+) {
+    let price: Amount = price.into();
+    world.run_failing_command(&format!(
+        "add --type sell --amount {} --price {} --currency {} --identifier {} --date {}",
+        amount, price.num, price.currency, ticker, date
+    ));
+}
+
+#[given("I have the following stock transactions with fees")]
+fn i_have_the_following_stock_transactions_with_fees(world: &mut BullboardWorld, step: &Step) {
+    if let Some(table) = step.table() {
+        for row in table.rows.iter().skip(1) {
+            let ticker: String = row[0].parse().unwrap();
+            let currency: String = row[1].parse().unwrap();
+            let amount: f64 = row[2].parse().unwrap();
+            let price: f64 = row[3].parse().unwrap();
+            let fee: f64 = row[4].parse().unwrap();
+            let date: String = row[5].parse().unwrap();
+
+            world.run_command(&format!(
+                "add --type buy --amount {} --price {} --fee {} --currency {} --identifier {} --date {}",
+                amount, price, fee, currency, ticker, date
+            ));
+        }
+    }
+}
+
+#[when("I sell the following stocks with fees")]
+fn i_sell_the_following_stocks_with_fees(world: &mut BullboardWorld, step: &Step) {
+    if let Some(table) = step.table() {
+        for row in table.rows.iter().skip(1) {
+            let ticker: String = row[0].parse().unwrap();
+            let currency: String = row[1].parse().unwrap();
+            let amount: f64 = row[2].parse().unwrap();
+            let price: f64 = row[3].parse().unwrap();
+            let fee: f64 = row[4].parse().unwrap();
+            let date: String = row[5].parse().unwrap();
+
+            world.run_command(&format!(
+                "add --type sell --amount {} --price {} --fee {} --currency {} --identifier {} --date {}",
+                amount, price, fee, currency, ticker, date
+            ));
+        }
+    }
+}
+
+#[when(expr = "I am charged a {string} fee on {string}")]
+fn i_am_charged_a_fee_on(world: &mut BullboardWorld, amount: String, date: String) {
+    let amount: Amount = amount.into();
+
+    world.run_command(&format!(
+        "add --type fee --price {} --currency {} --date {}",
+        amount.num, amount.currency, date
+    ));
+}
+
+#[when(expr = "I void event {int} on {string}")]
+fn i_void_event_on(world: &mut BullboardWorld, sequence: u64, date: String) {
+    let id = world.event_id(sequence);
+    world.run_command(&format!("void {} --date {}", id, date));
+}
+
+#[when(expr = "I amend the price of event {int} to {string} on {string}")]
+fn i_amend_the_price_of_event_to_on(
+    world: &mut BullboardWorld,
+    sequence: u64,
+    price: String,
+    date: String,
+) {
+    let id = world.event_id(sequence);
+    let price: Amount = price.into();
+    world.run_command(&format!(
+        "amend {} --price {} --currency {} --date {}",
+        id, price.num, price.currency, date
+    ));
+}
+
+#[when(expr = "I import the following CSV from my broker")]
+fn i_import_the_following_csv(world: &mut BullboardWorld, step: &Step) {
+    let file = world.write_import_file(step);
+    world.run_command(&format!("import {}", file));
+}
+
+#[when(expr = "I import the following price history")]
+fn i_import_the_following_price_history(world: &mut BullboardWorld, step: &Step) {
+    let file = world.write_import_file(step);
+    world.run_command(&format!("prices import {}", file));
+}
+
+#[when(expr = "I fetch prices on {string} from the following quotes")]
+fn i_fetch_prices_on_from_the_following_quotes(
+    world: &mut BullboardWorld,
+    step: &Step,
+    date: String,
+) {
+    let file = world.write_import_file(step);
+    world.run_command(&format!("prices fetch --quotes {} --date {}", file, date));
+}
+
+#[when(expr = "I try to import the following CSV from my broker")]
+fn i_try_to_import_the_following_csv(world: &mut BullboardWorld, step: &Step) {
+    let file = world.write_import_file(step);
+    world.run_failing_command(&format!("import {}", file));
+}
+
+#[when(expr = "I preview the import of the following CSV from my broker")]
+fn i_preview_the_import_of_the_following_csv(world: &mut BullboardWorld, step: &Step) {
+    let file = world.write_import_file(step);
+    world.run_command(&format!("import --dry-run {}", file));
+}
+
 // #[then(expr = "I should see {string}")]
 // fn i_should_see(world: &mut BullboardWorld, state: String) {
 //     assert!(
@@ -156,6 +503,21 @@ fn i_should_see_following(world: &mut BullboardWorld, step: &Step) {
     }
 }
 
+#[then(expr = "I should see the following error")]
+fn i_should_see_the_following_error(world: &mut BullboardWorld, step: &Step) {
+    if let Some(content) = step.docstring() {
+        assert_eq!(&world.last_command_error, content.trim_start());
+    }
+}
+
+/// CSV has no leading blank line, unlike the tables
+#[then(expr = "I should see the following CSV")]
+fn i_should_see_following_csv(world: &mut BullboardWorld, step: &Step) {
+    if let Some(content) = step.docstring() {
+        assert_eq!(&world.last_command_output, content.trim_start());
+    }
+}
+
 // fn normalize_whitespace(s: &str) -> String {
 //     s.split_whitespace().collect::<Vec<_>>().join(" ")
 // }
@@ -0,0 +1,82 @@
+Feature: Accounts
+
+  So that I can track my brokerage, pension and shared accounts separately
+  As a user
+  I want to keep the events of each account apart
+  And I want to see a dashboard that combines them
+
+  Background:
+    Given a database file to store events
+
+  Scenario: List accounts
+    Given I create the account "pension"
+    And I have the following stock transactions in "personal"
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 1      | 60    | 2021-10-1 |
+    When I list my accounts
+    Then I should see the following text
+      """
+      Accounts
+        pension
+        personal
+      """
+
+  Scenario: Dashboard of a single account
+    Given I deposit "1000 USD" on "2021-9-1" in "personal"
+    And I deposit "500 USD" on "2021-9-1" in "pension"
+    And I have the following stock transactions in "personal"
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 2      | 60    | 2021-10-1 |
+    And I have the following stock transactions in "pension"
+      | Ticker | Currency | Amount | Price | Date      |
+      | VWRL   | USD      | 4      | 100   | 2021-10-1 |
+    When I check my dashboard for "pension"
+    Then I should see the following text
+      """
+      Dashboard
+
+        Number of positions              1 
+        Total buying price      400.00 USD 
+        Cash                    100.00 USD 
+        Total value             100.00 USD 
+        Total dividend            0.00 USD 
+        Total withheld tax        0.00 USD 
+        Total fees                0.00 USD 
+        Total unrealized P/L      0.00 USD 
+        Total realized P/L        0.00 USD 
+        Total P/L                 0.00 USD 
+
+        Ticker    Amount    Dividend      Tax       Net dividend      Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        VWRL           4    0.00 USD    0.00 USD        0.00 USD     ??.?? ???         ??.?? ???        0.00 USD    ??.?? ???           
+        Cash                                                        100.00 USD                                                          
+      """
+
+  Scenario: Consolidated dashboard of several accounts
+    Given I deposit "1000 USD" on "2021-9-1" in "personal"
+    And I deposit "500 USD" on "2021-9-1" in "pension"
+    And I have the following stock transactions in "personal"
+      | Ticker | Currency | Amount | Price | Date      |
+      | VWRL   | USD      | 1      | 90    | 2021-11-1 |
+    And I have the following stock transactions in "pension"
+      | Ticker | Currency | Amount | Price | Date      |
+      | VWRL   | USD      | 4      | 100   | 2021-10-1 |
+    When I check my dashboard for "personal,pension"
+    Then I should see the following text
+      """
+      Dashboard
+
+        Number of positions               1 
+        Total buying price       490.00 USD 
+        Cash                    1010.00 USD 
+        Total value             1010.00 USD 
+        Total dividend             0.00 USD 
+        Total withheld tax         0.00 USD 
+        Total fees                 0.00 USD 
+        Total unrealized P/L       0.00 USD 
+        Total realized P/L         0.00 USD 
+        Total P/L                  0.00 USD 
+
+        Ticker    Amount    Dividend      Tax       Net dividend       Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        VWRL           5    0.00 USD    0.00 USD        0.00 USD      ??.?? ???         ??.?? ???        0.00 USD    ??.?? ???           
+        Cash                                                        1010.00 USD                                                          
+      """
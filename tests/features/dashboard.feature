@@ -8,7 +8,8 @@ Feature: Dashboard
     Given a database file to store events
 
   Scenario: Portfolio Table
-    Given I have the following stock transactions
+    Given I deposit "500.00 USD" on "2021-9-1"
+    And I have the following stock transactions
       | Ticker | Currency | Amount | Price | Date      |
       | AAPL   | USD      | 1      | 60    | 2021-10-1 |
       | AAPL   | USD      | 1      | 90    | 2021-11-1 |
@@ -26,21 +27,28 @@ Feature: Dashboard
       """
       Dashboard
 
-        Number of positions             3 
-        Total buying price     290.00 USD 
-        Total value            208.00 USD 
-        Total dividend           0.00 USD 
+        Number of positions              3 
+        Total buying price      290.00 USD 
+        Cash                    210.00 USD 
+        Total value             418.00 USD 
+        Total dividend            0.00 USD 
+        Total withheld tax        0.00 USD 
+        Total fees                0.00 USD 
+        Total unrealized P/L     -2.00 USD 
+        Total realized P/L        0.00 USD 
+        Total P/L                -2.00 USD 
 
-        Ticker    Amount    Dividend      Value 
-        AAPL           2    0.00 USD    142.00 USD 
-        ESTC           3    0.00 USD     66.00 USD 
-        TSLA           1    0.00 USD     ??.?? ??? 
+        Ticker    Amount    Dividend      Tax       Net dividend      Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        AAPL           2    0.00 USD    0.00 USD        0.00 USD    142.00 USD         -8.00 USD        0.00 USD    -8.00 USD           
+        ESTC           3    0.00 USD    0.00 USD        0.00 USD     66.00 USD          6.00 USD        0.00 USD     6.00 USD           
+        TSLA           1    0.00 USD    0.00 USD        0.00 USD     ??.?? ???         ??.?? ???        0.00 USD    ??.?? ???           
+        Cash                                                        210.00 USD                                                          
       """
-      # TODO: add columns: name, unrealized P/L, realized P/L, Total P/L
-      # TODO: add percentages of gains/losses for each position sinice last price check
+      # TODO: add columns: name
 
   Scenario: Dividend
-    Given I have the following stock transactions
+    Given I deposit "1500.00 USD" on "2021-9-1"
+    And I have the following stock transactions
       | Ticker  | Currency | Amount  | Price | Date      |
       | MSFT    | USD      | 5       | 60    | 2021-10-1 |
     When "MSFT" pays "0.62 USD" dividend per share on "2021-11-17"
@@ -53,37 +61,407 @@ Feature: Dashboard
       """
       Dashboard
 
-        Number of positions              1 
-        Total buying price     1200.00 USD 
-        Total value               0.00 USD 
-        Total dividend            3.10 USD 
+        Number of positions               1 
+        Total buying price      1200.00 USD 
+        Cash                     303.10 USD 
+        Total value              303.10 USD 
+        Total dividend             3.10 USD 
+        Total withheld tax         0.00 USD 
+        Total fees                 0.00 USD 
+        Total unrealized P/L       0.00 USD 
+        Total realized P/L         0.00 USD 
+        Total P/L                  3.10 USD 
 
-        Ticker    Amount    Dividend      Value 
-        MSFT          20    3.10 USD    ??.?? ??? 
+        Ticker    Amount    Dividend      Tax       Net dividend      Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        MSFT          20    3.10 USD    0.00 USD        3.10 USD     ??.?? ???         ??.?? ???        0.00 USD    ??.?? ???           
+        Cash                                                        303.10 USD                                                          
       """
 
   Scenario: Different currencies
+    Given I deposit "500.00 USD" on "2021-9-1"
+    And I deposit "200.00 EUR" on "2021-9-1"
+    And I have the following stock transactions
+      | Ticker  | Currency | Amount  | Price | Date      |
+      | MSFT    | USD      | 5       | 60    | 2021-10-1 |
+      | ASR-AS  | EUR      | 2       | 50    | 2021-11-1 |
+    When the prices change to the following values on "2021-11-17"
+      | Ticker | Currency | Price |
+      | MSFT   | USD      | 70    |
+      | ASR-AS | EUR      | 60    |
+    When I check my dashboard
+    Then I should see the following text
+      """
+      Dashboard
+
+        Number of positions              2 
+        Total buying price      100.00 EUR 
+                                300.00 USD 
+        Cash                    100.00 EUR 
+                                200.00 USD 
+        Total value             220.00 EUR 
+                                550.00 USD 
+        Total dividend            0.00 USD 
+        Total withheld tax        0.00 USD 
+        Total fees                0.00 USD 
+        Total unrealized P/L     20.00 EUR 
+                                 50.00 USD 
+        Total realized P/L        0.00 USD 
+        Total P/L                20.00 EUR 
+                                 50.00 USD 
+
+        Ticker    Amount    Dividend      Tax       Net dividend      Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        MSFT           5    0.00 USD    0.00 USD        0.00 USD    350.00 USD         50.00 USD        0.00 USD    50.00 USD           
+        ASR-AS         2    0.00 EUR    0.00 EUR        0.00 EUR    120.00 EUR         20.00 EUR        0.00 EUR    20.00 EUR           
+        Cash                                                        100.00 EUR                                                          
+        Cash                                                        200.00 USD                                                          
+      """
+
+  Scenario: Selling stocks
+    Given I deposit "500.00 USD" on "2021-9-1"
+    And I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 2      | 60    | 2021-10-1 |
+      | AAPL   | USD      | 2      | 80    | 2021-11-1 |
+      | TSLA   | USD      | 1      | 80    | 2021-12-1 |
+    When I sell the following stocks
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 1      | 90    | 2022-1-1  |
+      | TSLA   | USD      | 1      | 70    | 2022-1-2  |
+    When the prices change to the following values on "2022-1-3"
+      | Ticker | Currency | Price |
+      | AAPL   | USD      | 85    |
+    When I check my dashboard
+    Then I should see the following text
+      """
+      Dashboard
+
+        Number of positions              1 
+        Total buying price      360.00 USD 
+        Cash                    300.00 USD 
+        Total value             555.00 USD 
+        Total dividend            0.00 USD 
+        Total withheld tax        0.00 USD 
+        Total fees                0.00 USD 
+        Total unrealized P/L     35.00 USD 
+        Total realized P/L       20.00 USD 
+        Total P/L                55.00 USD 
+
+        Ticker    Amount    Dividend      Tax       Net dividend      Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        AAPL           3    0.00 USD    0.00 USD        0.00 USD    255.00 USD         35.00 USD       30.00 USD    65.00 USD           
+        TSLA           0    0.00 USD    0.00 USD        0.00 USD     ??.?? ???         ??.?? ???      -10.00 USD    ??.?? ???           
+        Cash                                                        300.00 USD                                                          
+      """
+
+  Scenario: Selling more stocks than held
     Given I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 2      | 60    | 2021-10-1 |
+    When I try to sell 3.0 "AAPL" at "90 USD" on "2022-1-1"
+    Then I should see the following error
+      """
+      Error: Oversold { identifier: StockIdentifier { ticker: "AAPL" }, held: 2.0, sold: 3.0 }
+      """
+
+  Scenario: Selling stocks using the average cost basis method
+    Given I deposit "500.00 USD" on "2021-9-1"
+    And I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 2      | 60    | 2021-10-1 |
+      | AAPL   | USD      | 2      | 80    | 2021-11-1 |
+    When I sell the following stocks
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 1      | 90    | 2022-1-1  |
+    When I check my dashboard using the "average" cost basis method
+    Then I should see the following text
+      """
+      Dashboard
+
+        Number of positions              1 
+        Total buying price      280.00 USD 
+        Cash                    310.00 USD 
+        Total value             310.00 USD 
+        Total dividend            0.00 USD 
+        Total withheld tax        0.00 USD 
+        Total fees                0.00 USD 
+        Total unrealized P/L      0.00 USD 
+        Total realized P/L       20.00 USD 
+        Total P/L                20.00 USD 
+
+        Ticker    Amount    Dividend      Tax       Net dividend      Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        AAPL           3    0.00 USD    0.00 USD        0.00 USD     ??.?? ???         ??.?? ???       20.00 USD    ??.?? ???           
+        Cash                                                        310.00 USD                                                          
+      """
+
+  Scenario: Price changes
+    Given I deposit "500.00 USD" on "2021-9-1"
+    And I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 2      | 60    | 2021-10-1 |
+      | ESTC   | USD      | 3      | 20    | 2022-1-1  |
+    When "AAPL" pays "0.50 USD" dividend per share on "2021-11-17"
+    When the prices change to the following values on "2022-1-2"
+      | Ticker | Currency | Price |
+      | AAPL   | USD      | 80    |
+      | ESTC   | USD      | 25    |
+    When the prices change to the following values on "2022-1-3"
+      | Ticker | Currency | Price |
+      | AAPL   | USD      | 72    |
+      | ESTC   | USD      | 26    |
+    When I check my dashboard
+    Then I should see the following text
+      """
+      Dashboard
+
+        Number of positions              2 
+        Total buying price      180.00 USD 
+        Cash                    321.00 USD 
+        Total value             543.00 USD 
+        Total dividend            1.00 USD 
+        Total withheld tax        0.00 USD 
+        Total fees                0.00 USD 
+        Total unrealized P/L     42.00 USD 
+        Total realized P/L        0.00 USD 
+        Total P/L                43.00 USD 
+
+        Ticker    Amount    Dividend      Tax       Net dividend      Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        AAPL           2    1.00 USD    0.00 USD        1.00 USD    144.00 USD         24.00 USD        0.00 USD    25.00 USD    -10.00% 
+        ESTC           3    0.00 USD    0.00 USD        0.00 USD     78.00 USD         18.00 USD        0.00 USD    18.00 USD      4.00% 
+        Cash                                                        321.00 USD                                                           
+      """
+
+  Scenario: Base currency
+    Given I deposit "500.00 USD" on "2021-9-1"
+    And I deposit "200.00 EUR" on "2021-9-1"
+    And I deposit "2000.00 JPY" on "2021-9-1"
+    And I have the following stock transactions
       | Ticker  | Currency | Amount  | Price | Date      |
       | MSFT    | USD      | 5       | 60    | 2021-10-1 |
       | ASR-AS  | EUR      | 2       | 50    | 2021-11-1 |
+      | SONY    | JPY      | 2       | 500   | 2021-11-1 |
+    When the exchange rate of "USD" is "0.85 EUR" on "2021-11-1"
     When the prices change to the following values on "2021-11-17"
       | Ticker | Currency | Price |
       | MSFT   | USD      | 70    |
       | ASR-AS | EUR      | 60    |
+      | SONY   | JPY      | 600   |
+    When I check my dashboard in "EUR"
+    Then I should see the following text
+      # NOTE: There is no rate for JPY, so SONY is flagged and left out of the totals
+      """
+      Dashboard
+
+        Number of positions                 3 
+        Total buying price         100.00 EUR 
+                                  1000.00 JPY 
+                                   300.00 USD 
+        Cash                       100.00 EUR 
+                                  1000.00 JPY 
+                                   200.00 USD 
+        Total value                220.00 EUR 
+                                  2200.00 JPY 
+                                   550.00 USD 
+        Total dividend               0.00 USD 
+        Total withheld tax           0.00 USD 
+        Total fees                   0.00 USD 
+        Total unrealized P/L        20.00 EUR 
+                                   200.00 JPY 
+                                    50.00 USD 
+        Total realized P/L           0.00 USD 
+        Total P/L                   20.00 EUR 
+                                   200.00 JPY 
+                                    50.00 USD 
+        Total value in EUR         687.50 EUR 
+        Total P/L in EUR            62.50 EUR 
+        Missing exchange rates            JPY 
+
+        Ticker    Amount    Dividend      Tax       Net dividend       Value       Unrealized P/L    Realized P/L    Total P/L     Change    Value in EUR 
+        SONY           2    0.00 JPY    0.00 JPY        0.00 JPY    1200.00 JPY        200.00 JPY        0.00 JPY    200.00 JPY                   no rate 
+        MSFT           5    0.00 USD    0.00 USD        0.00 USD     350.00 USD         50.00 USD        0.00 USD     50.00 USD                297.50 EUR 
+        ASR-AS         2    0.00 EUR    0.00 EUR        0.00 EUR     120.00 EUR         20.00 EUR        0.00 EUR     20.00 EUR                120.00 EUR 
+        Cash                                                         100.00 EUR                                                                100.00 EUR 
+        Cash                                                        1000.00 JPY                                                                   no rate 
+        Cash                                                         200.00 USD                                                                170.00 EUR 
+      """
+
+  Scenario: Stock split
+    Given I deposit "1000.00 USD" on "2021-9-1"
+    And I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 2      | 400   | 2021-10-1 |
+    When the prices change to the following values on "2021-12-1"
+      | Ticker | Currency | Price |
+      | AAPL   | USD      | 440   |
+    When "AAPL" splits "4:1" on "2022-1-1"
+    When the prices change to the following values on "2022-1-2"
+      | Ticker | Currency | Price |
+      | AAPL   | USD      | 121   |
+    When I check my dashboard
+    Then I should see the following text
+      """
+      Dashboard
+
+        Number of positions               1 
+        Total buying price       800.00 USD 
+        Cash                     200.00 USD 
+        Total value             1168.00 USD 
+        Total dividend             0.00 USD 
+        Total withheld tax         0.00 USD 
+        Total fees                 0.00 USD 
+        Total unrealized P/L     168.00 USD 
+        Total realized P/L         0.00 USD 
+        Total P/L                168.00 USD 
+
+        Ticker    Amount    Dividend      Tax       Net dividend      Value       Unrealized P/L    Realized P/L    Total P/L     Change 
+        AAPL           8    0.00 USD    0.00 USD        0.00 USD    968.00 USD        168.00 USD        0.00 USD    168.00 USD    10.00% 
+        Cash                                                        200.00 USD                                                           
+      """
+
+  Scenario: Cash
+    Given I deposit "1000.00 USD" on "2021-9-1"
+    And I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | MSFT   | USD      | 5      | 60    | 2021-10-1 |
+    When "MSFT" pays "0.62 USD" dividend per share on "2021-11-17"
+    When I sell the following stocks
+      | Ticker | Currency | Amount | Price | Date      |
+      | MSFT   | USD      | 2      | 70    | 2022-1-1  |
+    When I withdraw "250.00 USD" on "2022-1-2"
+    When I check my dashboard
+    Then I should see the following text
+      """
+      Dashboard
+
+        Number of positions              1 
+        Total buying price      300.00 USD 
+        Cash                    593.10 USD 
+        Total value             593.10 USD 
+        Total dividend            3.10 USD 
+        Total withheld tax        0.00 USD 
+        Total fees                0.00 USD 
+        Total unrealized P/L      0.00 USD 
+        Total realized P/L       20.00 USD 
+        Total P/L                23.10 USD 
+
+        Ticker    Amount    Dividend      Tax       Net dividend      Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        MSFT           3    3.10 USD    0.00 USD        3.10 USD     ??.?? ???         ??.?? ???       20.00 USD    ??.?? ???           
+        Cash                                                        593.10 USD                                                          
+      """
+
+  Scenario: Fees
+    Given I deposit "1000.00 USD" on "2021-9-1"
+    And I have the following stock transactions with fees
+      | Ticker | Currency | Amount | Price | Fee | Date      |
+      | MSFT   | USD      | 10     | 60    | 10  | 2021-10-1 |
+    When I sell the following stocks with fees
+      | Ticker | Currency | Amount | Price | Fee | Date      |
+      | MSFT   | USD      | 5      | 70    | 5   | 2021-12-1 |
+    When I am charged a "2.50 USD" fee on "2021-12-31"
+    When I check my dashboard
+    Then I should see the following text
+      """
+      Dashboard
+
+        Number of positions              1 
+        Total buying price      600.00 USD 
+        Cash                    732.50 USD 
+        Total value             732.50 USD 
+        Total dividend            0.00 USD 
+        Total withheld tax        0.00 USD 
+        Total fees               17.50 USD 
+        Total unrealized P/L      0.00 USD 
+        Total realized P/L       40.00 USD 
+        Total P/L                40.00 USD 
+
+        Ticker    Amount    Dividend      Tax       Net dividend      Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        MSFT           5    0.00 USD    0.00 USD        0.00 USD     ??.?? ???         ??.?? ???       40.00 USD    ??.?? ???           
+        Cash                                                        732.50 USD                                                          
+      """
+
+  Scenario: Dividend with tax withheld
+    Given I deposit "1000.00 USD" on "2021-9-1"
+    And I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | MSFT   | USD      | 10     | 60    | 2021-10-1 |
+    When "MSFT" pays "0.62 USD" dividend per share with "0.09 USD" tax withheld in "US" on "2021-11-17"
     When I check my dashboard
     Then I should see the following text
       """
       Dashboard
 
-        Number of positions             2 
-        Total buying price     100.00 EUR 
-                               300.00 USD 
-        Total value            120.00 EUR 
-                               350.00 USD 
-        Total dividend           0.00 USD 
+        Number of positions              1 
+        Total buying price      600.00 USD 
+        Cash                    405.30 USD 
+        Total value             405.30 USD 
+        Total dividend            6.20 USD 
+        Total withheld tax        0.90 USD 
+        Total fees                0.00 USD 
+        Total unrealized P/L      0.00 USD 
+        Total realized P/L        0.00 USD 
+        Total P/L                 5.30 USD 
+
+        Ticker    Amount    Dividend      Tax       Net dividend      Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        MSFT          10    6.20 USD    0.90 USD        5.30 USD     ??.?? ???         ??.?? ???        0.00 USD    ??.?? ???           
+        Cash                                                        405.30 USD                                                          
+      """
+
+  Scenario: Dashboard as of a date
+    Given I deposit "1000.00 USD" on "2021-9-1"
+    And I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | MSFT   | USD      | 5      | 60    | 2021-10-1 |
+      | AAPL   | USD      | 2      | 80    | 2022-1-3  |
+    When the prices change to the following values on "2021-12-31"
+      | Ticker | Currency | Price |
+      | MSFT   | USD      | 70    |
+    When the prices change to the following values on "2022-6-30"
+      | Ticker | Currency | Price |
+      | MSFT   | USD      | 90    |
+    When I check my dashboard as of "2021-12-31"
+    Then I should see the following text
+      """
+      Dashboard
+
+        Number of positions               1 
+        Total buying price       300.00 USD 
+        Cash                     700.00 USD 
+        Total value             1050.00 USD 
+        Total dividend             0.00 USD 
+        Total withheld tax         0.00 USD 
+        Total fees                 0.00 USD 
+        Total unrealized P/L      50.00 USD 
+        Total realized P/L         0.00 USD 
+        Total P/L                 50.00 USD 
+
+        Ticker    Amount    Dividend      Tax       Net dividend      Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        MSFT           5    0.00 USD    0.00 USD        0.00 USD    350.00 USD         50.00 USD        0.00 USD    50.00 USD           
+        Cash                                                        700.00 USD                                                          
+      """
+
+  Scenario: Voided and amended events
+    Given I deposit "1000 USD" on "2021-9-1"
+    And I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 1      | 60    | 2021-10-1 |
+      | TSLA   | USD      | 1      | 80    | 2021-12-1 |
+    When I amend the price of event 2 to "65 USD" on "2021-12-15"
+    When I void event 3 on "2021-12-20"
+    When I check my dashboard
+    Then I should see the following text
+      """
+      Dashboard
+
+        Number of positions              1 
+        Total buying price       65.00 USD 
+        Cash                    935.00 USD 
+        Total value             935.00 USD 
+        Total dividend            0.00 USD 
+        Total withheld tax        0.00 USD 
+        Total fees                0.00 USD 
+        Total unrealized P/L      0.00 USD 
+        Total realized P/L        0.00 USD 
+        Total P/L                 0.00 USD 
 
-        Ticker    Amount    Dividend      Value 
-        MSFT           5    0.00 USD    350.00 USD 
-        ASR-AS         2    0.00 EUR    120.00 EUR 
+        Ticker    Amount    Dividend      Tax       Net dividend      Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        AAPL           1    0.00 USD    0.00 USD        0.00 USD     ??.?? ???         ??.?? ???        0.00 USD    ??.?? ???           
+        Cash                                                        935.00 USD                                                          
       """
@@ -0,0 +1,111 @@
+Feature: Import
+
+  So that I don't have to add every transaction by hand
+  As a user
+  I want to import the CSV export of my broker
+
+  Background:
+    Given a database file to store events
+
+  Scenario: Preview an import
+    When I preview the import of the following CSV from my broker
+      """
+      Date,Type,Ticker,Amount,Price,Currency
+      2021-10-01,buy,MSFT,5,60.00,USD
+      2021-11-01,deposit,,,100.00,USD
+      2021-11-17,dividend,MSFT,,0.62,USD
+      """
+    Then I should see the following text
+      """
+      My Journal
+           Date         Type      Ticker    Amount      Price        Total 
+        2021-10-01    Buy         MSFT           5    60.00 USD    300.00 USD 
+        2021-11-17    Dividend    MSFT           1     0.62 USD      0.62 USD 
+
+      Would import 2 events, 0 duplicates and 1 unknown rows skipped
+      """
+
+  Scenario: Importing a row without a currency
+    When I try to import the following CSV from my broker
+      """
+      Date,Type,Ticker,Amount,Price,Currency
+      2021-10-01,buy,MSFT,5,60.00,USD
+      2021-11-17,dividend,MSFT,,0.62,
+      """
+    Then I should see the following error
+      """
+      Error: InvalidValue { line: 3, column: "Currency", value: "" }
+      """
+
+  Scenario: Importing the same file twice
+    When I import the following CSV from my broker
+      """
+      Date,Type,Ticker,Amount,Price,Currency
+      2021-10-01,buy,MSFT,5,60.00,USD
+      2021-11-17,dividend,MSFT,,0.62,USD
+      2021-12-01,price,MSFT,,70.00,USD
+      """
+    When I import the following CSV from my broker
+      """
+      Date,Type,Ticker,Amount,Price,Currency
+      2021-10-01,buy,MSFT,5,60.00,USD
+      2021-11-17,dividend,MSFT,,0.62,USD
+      2021-12-01,price,MSFT,,70.00,USD
+      2022-01-03,buy,MSFT,1,72.00,USD
+      """
+    Then I should see the following text
+      """
+      Imported 1 events, 3 duplicates and 0 unknown rows skipped
+      """
+    When I check my journal
+    Then I should see the following text
+      """
+      My Journal
+           Date         Type      Ticker    Amount      Price        Total 
+        2021-10-01    Buy         MSFT           5    60.00 USD    300.00 USD 
+        2021-11-17    Dividend    MSFT           1     0.62 USD      0.62 USD 
+        2022-01-03    Buy         MSFT           1    72.00 USD     72.00 USD 
+      """
+
+  Scenario: Importing a price history twice
+    Given I deposit "1000.00 USD" on "2021-9-1"
+    And I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | MSFT   | USD      | 5      | 60    | 2021-10-1 |
+    When I import the following price history
+      """
+      date,ticker,close,currency
+      2021-10-29,MSFT,65.00,USD
+      2021-11-30,MSFT,70.00,USD
+      """
+    When I import the following price history
+      """
+      date,ticker,close,currency
+      2021-10-29,MSFT,65.00,USD
+      2021-11-30,MSFT,70.00,USD
+      2021-12-31,MSFT,77.00,USD
+      """
+    Then I should see the following text
+      """
+      Imported 1 prices, 2 already known skipped
+      """
+    When I check my dashboard
+    Then I should see the following text
+      """
+      Dashboard
+
+        Number of positions               1 
+        Total buying price       300.00 USD 
+        Cash                     700.00 USD 
+        Total value             1085.00 USD 
+        Total dividend             0.00 USD 
+        Total withheld tax         0.00 USD 
+        Total fees                 0.00 USD 
+        Total unrealized P/L      85.00 USD 
+        Total realized P/L         0.00 USD 
+        Total P/L                 85.00 USD 
+
+        Ticker    Amount    Dividend      Tax       Net dividend      Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        MSFT           5    0.00 USD    0.00 USD        0.00 USD    385.00 USD         85.00 USD        0.00 USD    85.00 USD    10.00% 
+        Cash                                                        700.00 USD                                                          
+      """
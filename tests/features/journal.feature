@@ -27,3 +27,179 @@ Feature: Journal
         2021-12-01    Buy         TSLA           1    80.00 USD    80.00 USD 
         2022-01-01    Buy         ESTC           3    20.00 USD    60.00 USD 
       """
+
+  Scenario: Journal in base currency
+    Given I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 1      | 60    | 2021-10-1 |
+      | AAPL   | USD      | 1      | 90    | 2021-11-1 |
+    When the exchange rate of "USD" is "0.80 EUR" on "2021-10-1"
+    When the exchange rate of "USD" is "0.90 EUR" on "2021-10-15"
+    When I check my journal in "EUR"
+    Then I should see the following text
+      """
+      My Journal
+           Date       Type    Ticker    Amount      Price        Total      Total in EUR 
+        2021-10-01    Buy     AAPL           1    60.00 USD    60.00 USD       48.00 EUR 
+        2021-11-01    Buy     AAPL           1    90.00 USD    90.00 USD       81.00 EUR 
+      """
+
+  Scenario: Stock split in the journal
+    Given I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 2      | 400   | 2021-10-1 |
+    When "AAPL" splits "4:1" on "2022-1-1"
+    When I check my journal
+    Then I should see the following text
+      """
+      My Journal
+           Date       Type     Ticker    Amount      Price         Total 
+        2021-10-01    Buy      AAPL           2    400.00 USD    800.00 USD 
+        2022-01-01    Split    AAPL          x4                             
+      """
+
+  Scenario: Cash deposits and withdrawals
+    Given I deposit "1000.00 USD" on "2021-9-1"
+    When I withdraw "250.00 USD" on "2022-1-2"
+    When I check my journal
+    Then I should see the following text
+      """
+      My Journal
+           Date          Type       Ticker    Amount       Price          Total 
+        2021-09-01    Deposit       USD            1    1000.00 USD    1000.00 USD 
+        2022-01-02    Withdrawal    USD            1     250.00 USD     250.00 USD 
+      """
+
+  Scenario: Fees in the journal
+    Given I deposit "1000.00 USD" on "2021-9-1"
+    And I have the following stock transactions with fees
+      | Ticker | Currency | Amount | Price | Fee | Date      |
+      | MSFT   | USD      | 10     | 60    | 10  | 2021-10-1 |
+    When I sell the following stocks with fees
+      | Ticker | Currency | Amount | Price | Fee | Date      |
+      | MSFT   | USD      | 5      | 70    | 5   | 2021-12-1 |
+    When I am charged a "2.50 USD" fee on "2021-12-31"
+    When I check my journal
+    Then I should see the following text
+      """
+      My Journal
+           Date        Type      Ticker    Amount       Price          Total 
+        2021-09-01    Deposit    USD            1    1000.00 USD    1000.00 USD 
+        2021-10-01    Buy        MSFT          10      60.00 USD     610.00 USD 
+        2021-12-01    Sell       MSFT           5      70.00 USD     345.00 USD 
+        2021-12-31    Fee        USD            1       2.50 USD       2.50 USD 
+      """
+
+  Scenario: Journal as JSON
+    Given I deposit "1000.00 USD" on "2021-9-1"
+    And I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | MSFT   | USD      | 10     | 60    | 2021-10-1 |
+    When I check my journal as "JSON"
+    Then I should see the following text
+      """
+      {
+        "base_currency": null,
+        "rows": [
+          {
+            "date": "2021-09-01",
+            "rtype": "Deposit",
+            "identifier": {
+              "ticker": "USD"
+            },
+            "amount": 1.0,
+            "price": {
+              "num": "1000.00",
+              "currency": "USD"
+            },
+            "total": {
+              "num": "1000.00",
+              "currency": "USD"
+            },
+            "total_in_base_currency": null
+          },
+          {
+            "date": "2021-10-01",
+            "rtype": "Buy",
+            "identifier": {
+              "ticker": "MSFT"
+            },
+            "amount": 10.0,
+            "price": {
+              "num": "60",
+              "currency": "USD"
+            },
+            "total": {
+              "num": "600",
+              "currency": "USD"
+            },
+            "total_in_base_currency": null
+          }
+        ]
+      }
+      """
+
+  Scenario: Journal as CSV
+    Given I deposit "1000.00 USD" on "2021-9-1"
+    And I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | MSFT   | USD      | 10     | 60    | 2021-10-1 |
+    When "MSFT" pays "0.62 USD" dividend per share on "2021-11-17"
+    When I check my journal as "CSV"
+    Then I should see the following CSV
+      """
+      date,type,ticker,amount,price,price_currency,total,total_currency
+      2021-09-01,Deposit,USD,1,1000.00,USD,1000.00,USD
+      2021-10-01,Buy,MSFT,10,60,USD,600,USD
+      2021-11-17,Dividend,MSFT,1,0.62,USD,0.62,USD
+      """
+
+  Scenario: Corrections in the journal
+    Given I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 1      | 60    | 2021-10-1 |
+      | TSLA   | USD      | 1      | 80    | 2021-12-1 |
+    When I amend the price of event 1 to "65 USD" on "2021-12-15"
+    When I void event 2 on "2021-12-20"
+    When I check my journal
+    Then I should see the following text
+      """
+      My Journal
+           Date       Type     Ticker    Amount      Price        Total 
+        2021-10-01    Buy      AAPL           1    60.00 USD    60.00 USD 
+        2021-12-01    Buy      TSLA           1    80.00 USD    80.00 USD 
+        2021-12-15    Amend    AAPL           1    65.00 USD    65.00 USD 
+        2021-12-20    Void     TSLA           1    80.00 USD    80.00 USD 
+      """
+
+  Scenario: History of a single asset
+    Given I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 1      | 60    | 2021-10-1 |
+      | TSLA   | USD      | 1      | 80    | 2021-10-5 |
+      | AAPL   | USD      | 1      | 90    | 2021-11-1 |
+    When "AAPL" pays "0.25 USD" dividend per share on "2021-11-17"
+    When the exchange rate of "USD" is "0.80 EUR" on "2021-10-1"
+    When I check the history of "AAPL" in "EUR"
+    Then I should see the following text
+      """
+      My Journal
+           Date         Type      Ticker    Amount      Price        Total      Total in EUR 
+        2021-10-01    Buy         AAPL           1    60.00 USD    60.00 USD       48.00 EUR 
+        2021-11-01    Buy         AAPL           1    90.00 USD    90.00 USD       72.00 EUR 
+        2021-11-17    Dividend    AAPL           1     0.25 USD     0.25 USD        0.20 EUR 
+      """
+
+  Scenario: Journal of a period
+    Given I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 1      | 60    | 2021-10-1 |
+      | TSLA   | USD      | 1      | 80    | 2021-10-5 |
+      | AAPL   | USD      | 1      | 90    | 2021-11-1 |
+    When I check my journal from "2021-10-2" until "2021-10-31"
+    Then I should see the following text
+      """
+      My Journal
+           Date       Type    Ticker    Amount      Price        Total 
+        2021-10-05    Buy     TSLA           1    80.00 USD    80.00 USD 
+      """
@@ -0,0 +1,48 @@
+Feature: Prices
+
+  So that I don't have to type in prices by hand
+  As a user
+  I want to fetch the prices of the assets I hold
+
+  Background:
+    Given a database file to store events
+
+  Scenario: Fetch prices from a quotes file
+    Given I deposit "1000.00 USD" on "2021-9-1"
+    And I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | MSFT   | USD      | 5      | 60    | 2021-10-1 |
+      | AAPL   | USD      | 2      | 150   | 2021-10-1 |
+    When I fetch prices on "2021-11-30" from the following quotes
+      """
+      date,ticker,close,currency
+      2021-10-29,MSFT,65.00,USD
+      2021-11-30,MSFT,70.00,USD
+      2021-12-31,MSFT,77.00,USD
+      """
+    Then I should see the following text
+      """
+      Fetched 1 prices, 0 already known skipped
+      No price found for AAPL
+      """
+    When I check my dashboard
+    Then I should see the following text
+      """
+      Dashboard
+
+        Number of positions              2 
+        Total buying price      600.00 USD 
+        Cash                    400.00 USD 
+        Total value             750.00 USD 
+        Total dividend            0.00 USD 
+        Total withheld tax        0.00 USD 
+        Total fees                0.00 USD 
+        Total unrealized P/L     50.00 USD 
+        Total realized P/L        0.00 USD 
+        Total P/L                50.00 USD 
+
+        Ticker    Amount    Dividend      Tax       Net dividend      Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        MSFT           5    0.00 USD    0.00 USD        0.00 USD    350.00 USD         50.00 USD        0.00 USD    50.00 USD           
+        AAPL           2    0.00 USD    0.00 USD        0.00 USD     ??.?? ???         ??.?? ???        0.00 USD    ??.?? ???           
+        Cash                                                        400.00 USD                                                          
+      """
@@ -0,0 +1,60 @@
+Feature: Storage
+
+  So that I can keep my portfolio history in version control
+  As a user
+  I want to store my events in a plain text file, one event per line
+
+  Scenario: Dashboard from a JSON Lines file
+    Given a JSON Lines file to store events
+    And I deposit "1000 USD" on "2021-9-1"
+    And I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 2      | 60    | 2021-10-1 |
+    When I check my dashboard
+    Then I should see the following text
+      """
+      Dashboard
+
+        Number of positions              1 
+        Total buying price      120.00 USD 
+        Cash                    880.00 USD 
+        Total value             880.00 USD 
+        Total dividend            0.00 USD 
+        Total withheld tax        0.00 USD 
+        Total fees                0.00 USD 
+        Total unrealized P/L      0.00 USD 
+        Total realized P/L        0.00 USD 
+        Total P/L                 0.00 USD 
+
+        Ticker    Amount    Dividend      Tax       Net dividend      Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        AAPL           2    0.00 USD    0.00 USD        0.00 USD     ??.?? ???         ??.?? ???        0.00 USD    ??.?? ???           
+        Cash                                                        880.00 USD                                                          
+      """
+
+  Scenario: Recover when writing the last event was cut off
+    Given a JSON Lines file to store events
+    And I deposit "1000 USD" on "2021-9-1"
+    And I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | AAPL   | USD      | 2      | 60    | 2021-10-1 |
+    And writing the last event was cut off
+    And I deposit "500 USD" on "2021-11-1"
+    When I check my dashboard
+    Then I should see the following text
+      """
+      Dashboard
+
+        Number of positions               0 
+        Total buying price         0.00 USD 
+        Cash                    1500.00 USD 
+        Total value             1500.00 USD 
+        Total dividend             0.00 USD 
+        Total withheld tax         0.00 USD 
+        Total fees                 0.00 USD 
+        Total unrealized P/L       0.00 USD 
+        Total realized P/L         0.00 USD 
+        Total P/L                  0.00 USD 
+
+        Ticker    Amount    Dividend    Tax    Net dividend       Value       Unrealized P/L    Realized P/L    Total P/L    Change 
+        Cash                                                   1500.00 USD                                                          
+      """
@@ -0,0 +1,28 @@
+Feature: Dividend withholding
+
+  So that I can reclaim the tax withheld on my dividends
+  As a user
+  I want to see the tax withheld per country and year
+
+  Background:
+    Given a database file to store events
+
+  Scenario: Tax withheld per country and year
+    Given I deposit "2000.00 USD" on "2021-9-1"
+    And I deposit "500.00 EUR" on "2021-9-1"
+    And I have the following stock transactions
+      | Ticker | Currency | Amount | Price | Date      |
+      | MSFT   | USD      | 10     | 60    | 2021-10-1 |
+      | ASML   | EUR      | 5      | 80    | 2021-10-1 |
+    When "MSFT" pays "0.62 USD" dividend per share with "0.09 USD" tax withheld in "US" on "2021-11-17"
+    When "MSFT" pays "0.62 USD" dividend per share with "0.09 USD" tax withheld in "US" on "2022-2-17"
+    When "ASML" pays "1.20 EUR" dividend per share with "0.18 EUR" tax withheld in "NL" on "2022-5-1"
+    When I check my withholding report
+    Then I should see the following text
+      """
+      Dividend withholding
+        Country    Year     Gross      Withheld tax      Net 
+        NL         2022    6.00 EUR        0.90 EUR    5.10 EUR 
+        US         2021    6.20 USD        0.90 USD    5.30 USD 
+        US         2022    6.20 USD        0.90 USD    5.30 USD 
+      """